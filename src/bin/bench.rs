@@ -0,0 +1,174 @@
+//! Searches a fixed set of positions to a fixed depth and reports the searched nodes.
+//! Used to compare changes to the search.
+//! With `--tactics`, searches a set of positions with a known best move instead,
+//! each with the same number of nodes, and reports how many are solved.
+//!
+//! Usage: bench [DEPTH] [--tactics NODES] [--no-lmr] [--no-futility] [--single-reply]
+//!              [--capture-extensions] [--promotion-extensions] [--max-extensions N]
+//!              [--threads N]
+
+use std::time::{Duration, Instant};
+
+use checkers_checkerboard::game::*;
+
+const BENCH_POSITIONS: [&str; 10] = [
+    "B:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12",
+    "W:W13,19,21,24,25,26,27,28,29,30,31,32:B1,2,3,5,6,7,8,9,10,11,12,18",
+    "W:W19,21,23,25,26,27,28,29,30,31,32:B1,2,3,4,6,7,8,12,13,14,15",
+    "W:W18,19,21,22,23,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,9,11,12,14,20",
+    "B:W21,22,23,27,28,29,30,31,32:B1,2,3,4,7,8,11,12,14",
+    "B:W6,21,24,27,29,30,31,32:B1,3,4,7,8,12,18",
+    "B:W21,26,29,31:B4,8,10,12",
+    "B:WK7,21,27:B12,K19,22",
+    "B:W6,K10:B12,K19,K24",
+    "B:WK10,K16:B12,K18",
+];
+
+/// Positions where the best move wins material a few plies later, with that move.
+const TACTICAL_POSITIONS: [(&str, &str); 27] = [
+    ("W:W21,23,24,26,29,30,32:B1,3,4,9,12,13,15", "26-22"),
+    ("B:W19,20,21,22,23,29:B4,5,7,12,13,14", "7-11"),
+    ("B:W18,19,21,24,29,30,31,32:B1,3,4,5,6,7,8,10", "8-11"),
+    ("W:WK1,9,28,31:B3,4,15,19", "1-6"),
+    ("W:W10,21,22,26:B4,8,12,14", "26-23"),
+    ("W:W21,24,29,31,32:B1,2,4,12,18", "31-26"),
+    ("B:W5,13,21,24,28,29:B1,3,4,12,16,18", "18-22"),
+    ("B:W13,16,21,22,28,29,31,32:B1,2,4,7,12,14,15,19", "14-18"),
+    ("B:W17,18,19,21,23,25:B2,5,10,11,12,14", "2-7"),
+    ("B:W15,18,19,20,29:B4,12,17,K26", "26-22"),
+    (
+        "W:W20,21,22,24,26,28,29,30,31,32:B1,2,3,4,7,8,11,12,13,17,23",
+        "21x14",
+    ),
+    ("W:W9,19,21,23,29,30,31,32:B2,4,5,7,8,12,16", "29-25"),
+    ("B:W16,17,19,21,29,32:B1,2,4,7,12,14", "14-18"),
+    ("B:W15,20,21,29,32:B4,8,9,12,14", "8-11"),
+    ("W:W13,14,22,23,32:B2,4,5,20,28", "14-10"),
+    ("B:W5,21,25,27:B11,13,18,19", "11-16"),
+    ("W:W21,22,23,31,32:B8,9,12,13,14", "31-27"),
+    ("B:W18,19,23,24,29,30,32:B1,2,3,10,11,12,17", "2-6"),
+    ("W:W9,K10,26,28,29:B4,15,17,19,21", "10-14"),
+    (
+        "W:W17,19,21,22,23,24,28,29,30,31,32:B1,3,4,5,6,7,9,10,11,12,14",
+        "30-26",
+    ),
+    ("W:W15,21,24,25,26,27,29,30:B1,3,4,7,9,12,16,18", "24-20"),
+    ("W:WK7,17,20,21,29,31:B4,5,12,15,18,19", "7-10"),
+    ("W:WK7,17,22:B4,6,K16", "22-18"),
+    ("B:W15,19,21,28,29,31,32:B1,2,3,8,10,12,22", "1-6"),
+    ("B:W15,20,21,29,30,32:B1,3,4,6,12,14", "4-8"),
+    ("B:W11,13,21,28,32:B1,3,4,15,K26", "26-23"),
+    ("W:W19,20,23,28,29,31:B5,8,10,11,12,14", "31-26"),
+];
+
+fn main() {
+    let mut depth = 12;
+    let mut tactics_nodes = None;
+    let mut config = EngineConfig::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-lmr" => config.late_move_reductions = false,
+            "--no-futility" => config.futility_pruning = false,
+            "--single-reply" => config.single_reply_extensions = true,
+            "--capture-extensions" => config.capture_extensions = true,
+            "--promotion-extensions" => config.promotion_extensions = true,
+            "--max-extensions" => {
+                config.max_extensions = args
+                    .next()
+                    .and_then(|max_extensions| max_extensions.parse().ok())
+                    .expect("invalid extension limit")
+            }
+            "--tactics" => {
+                tactics_nodes = args
+                    .next()
+                    .and_then(|nodes| nodes.parse().ok())
+                    .map(Some)
+                    .expect("invalid node count")
+            }
+            "--threads" => {
+                config.threads = args
+                    .next()
+                    .and_then(|threads| threads.parse().ok())
+                    .expect("invalid thread count")
+            }
+            depth_arg => depth = depth_arg.parse().expect("invalid depth"),
+        }
+    }
+
+    match tactics_nodes {
+        Some(nodes) => tactics(nodes, &config),
+        None => bench(depth, &config),
+    }
+}
+
+fn bench(depth: usize, config: &EngineConfig) {
+    let mut total_nodes = 0;
+    let start_time = Instant::now();
+
+    for fen in BENCH_POSITIONS {
+        let mut game = Game::from_fen(fen).expect("invalid bench position");
+        let mut engine = Engine::with_endgame_table(Duration::MAX, EndgameTable::new());
+        engine.config = config.clone();
+
+        let position_start_time = Instant::now();
+        let score = engine.search_to_depth(&mut game, depth);
+
+        println!(
+            "{:<70} {:>12} nodes {:>8} ms  {}",
+            fen,
+            engine.searched_nodes,
+            position_start_time.elapsed().as_millis(),
+            Engine::translate_score(score, game.side_to_move.clone())
+        );
+
+        total_nodes += engine.searched_nodes;
+    }
+
+    let elapsed_time = start_time.elapsed();
+    println!(
+        "Total: {} nodes in {} ms ({} nps)",
+        total_nodes,
+        elapsed_time.as_millis(),
+        (total_nodes as f64 / elapsed_time.as_secs_f64()) as u64
+    );
+}
+
+fn tactics(nodes: usize, config: &EngineConfig) {
+    let mut solved = 0;
+    let start_time = Instant::now();
+
+    for (fen, best_move) in TACTICAL_POSITIONS {
+        let mut game = Game::from_fen(fen).expect("invalid tactical position");
+        let mut engine = Engine::with_endgame_table(Duration::MAX, EndgameTable::new());
+        engine.config = config.clone();
+
+        engine.search_with_limits(&mut game, SearchLimits::nodes(nodes));
+        let found_move = engine.best_move.clone().unwrap().to_string();
+
+        if found_move == best_move {
+            solved += 1;
+        }
+
+        println!(
+            "{:<70} {:>8} {:>8}  {}",
+            fen,
+            best_move,
+            found_move,
+            if found_move == best_move {
+                "ok"
+            } else {
+                "missed"
+            }
+        );
+    }
+
+    println!(
+        "Solved: {}/{} with {} nodes each in {} ms",
+        solved,
+        TACTICAL_POSITIONS.len(),
+        nodes,
+        start_time.elapsed().as_millis()
+    );
+}
@@ -0,0 +1,104 @@
+//! Checks an endgame database for positions whose value does not match their successors.
+//!
+//! Usage: db_check [--db PATH] [--slice SLICE]... [--sample N] [--depth N]
+//!                 [--engine-depth N] [--side black|white|both]
+//!
+//! Without `--slice` every slice that is entirely won, lost or drawn is checked.
+//! Without `--sample` every position of a slice is checked.
+
+use checkers_checkerboard::game::*;
+
+fn main() {
+    let mut db_path = EndgameTable::DEFAULT_PATH.to_string();
+    let mut slices = vec![];
+    let mut samples = None;
+    let mut resolve_depth = 2;
+    let mut engine_depth = 0;
+    let mut sides = vec![Color::Black, Color::White];
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().expect("missing value for argument");
+
+        match arg.as_str() {
+            "--db" => db_path = value(),
+            "--slice" => slices.push(value()),
+            "--sample" => samples = Some(value().parse().expect("invalid sample count")),
+            "--depth" => resolve_depth = value().parse().expect("invalid depth"),
+            "--engine-depth" => engine_depth = value().parse().expect("invalid engine depth"),
+            "--side" => {
+                sides = match value().as_str() {
+                    "black" => vec![Color::Black],
+                    "white" => vec![Color::White],
+                    "both" => vec![Color::Black, Color::White],
+                    side => panic!("invalid side {}", side),
+                }
+            }
+            arg => panic!("unknown argument {}", arg),
+        }
+    }
+
+    let endgame_table = EndgameTable::from_db(db_path).expect("could not read database");
+
+    if slices.is_empty() {
+        slices = endgame_table
+            .slices()
+            .filter(|(_, flag)| {
+                matches!(
+                    flag,
+                    EndgameTableFlag::BlackWin
+                        | EndgameTableFlag::WhiteWin
+                        | EndgameTableFlag::Draw
+                )
+            })
+            .map(|(slice, _)| slice.clone())
+            .collect();
+        slices.sort();
+    }
+
+    let mut checker = EndgameTableChecker::new(&endgame_table).with_engine_depth(engine_depth);
+    checker.sides = sides;
+    checker.resolve_depth = resolve_depth;
+
+    let mut total_checked = 0;
+    let mut total_inconsistencies = 0;
+
+    for slice in slices {
+        let report = match samples {
+            Some(samples) => checker.sample_slice(&slice, samples),
+            None => checker.check_slice(&slice),
+        };
+
+        let report = match report {
+            Ok(report) => report,
+            Err(error) => {
+                println!("{}", error);
+                continue;
+            }
+        };
+
+        println!(
+            "{} {:?}: {} checked, {} skipped, {} inconsistent",
+            report.slice,
+            report.flag,
+            report.checked,
+            report.skipped,
+            report.inconsistencies.len()
+        );
+
+        for inconsistency in report.inconsistencies.iter() {
+            println!(
+                "  {} #{} {}: {}",
+                inconsistency.slice, inconsistency.index, inconsistency.fen, inconsistency.reason
+            );
+        }
+
+        total_checked += report.checked;
+        total_inconsistencies += report.inconsistencies.len();
+    }
+
+    println!(
+        "Total: {} checked, {} inconsistent",
+        total_checked, total_inconsistencies
+    );
+}
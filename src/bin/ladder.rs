@@ -0,0 +1,96 @@
+//! Plays every strength level of the engine against the level below it and estimates their Elo.
+//! Every pair plays each of the 7 first moves as an opening, once with each colour.
+//! Level 1 is anchored at 0 Elo.
+//!
+//! Usage: ladder [MOVE_TIME_MS]
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use checkers_checkerboard::game::*;
+
+/// Games that are not over after this many plies are counted as draws.
+const MAX_PLIES: usize = 300;
+
+fn create_engine(level: usize, endgame_table: &Arc<EndgameTable>) -> Engine {
+    let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table.clone());
+    engine.config.strength = Strength::level(level);
+    engine.config.transposition_table_size = 16;
+    engine
+}
+
+/// Play a game from the opening and return the result for black, 1 for a win and 0.5 for a draw.
+fn play(
+    black: &mut Engine,
+    white: &mut Engine,
+    opening: &MoveSequence,
+    limits: &SearchLimits,
+) -> f64 {
+    let mut game = Game::new();
+    game.make_move_sequence(opening);
+
+    while game.ply < MAX_PLIES {
+        if game.is_draw() {
+            return 0.5;
+        } else if game.is_black_win() {
+            return 1.0;
+        } else if game.is_white_win() {
+            return 0.0;
+        }
+
+        let player = match game.side_to_move {
+            Color::Black => &mut *black,
+            Color::White => &mut *white,
+        };
+        let result = player.solve(&game, limits);
+        game.make_move_sequence(&result.best_move.unwrap());
+    }
+
+    0.5
+}
+
+/// Elo difference that makes the expected score `score`.
+fn elo_difference(score: f64, games: usize) -> f64 {
+    // A perfect score has no finite difference, count it as half a game less.
+    let score = score.clamp(0.5 / games as f64, 1.0 - 0.5 / games as f64);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn main() {
+    let move_time = std::env::args()
+        .nth(1)
+        .map_or(100, |arg| arg.parse().expect("invalid move time"));
+    let limits = SearchLimits::move_time(Duration::from_millis(move_time));
+    let openings = Game::new().generate_move_sequences();
+    // Loaded once and shared by all engines.
+    let endgame_table = match Path::new(EndgameTable::DEFAULT_PATH).exists() {
+        true => EndgameTable::shared(),
+        false => Arc::new(EndgameTable::new()),
+    };
+
+    let mut elo = 0.0;
+    println!("level  score  elo");
+    println!("{:>5}  {:>5}  {:>4}", 1, "-", 0);
+
+    for level in 2..=Strength::MAX_LEVEL {
+        let mut stronger = create_engine(level, &endgame_table);
+        let mut weaker = create_engine(level - 1, &endgame_table);
+        let mut score = 0.0;
+        let mut games = 0;
+
+        for opening in openings.iter() {
+            score += play(&mut stronger, &mut weaker, opening, &limits);
+            score += 1.0 - play(&mut weaker, &mut stronger, opening, &limits);
+            games += 2;
+        }
+
+        elo += elo_difference(score / games as f64, games);
+        println!(
+            "{:>5}  {:>5}  {:>4.0}",
+            level,
+            format!("{}/{}", score, games),
+            elo
+        );
+    }
+}
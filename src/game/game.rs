@@ -2,9 +2,9 @@
 
 use std::time::Duration;
 
-use crate::{game::*, helpers::PositionMapper, transposition_table::TranspositionTable};
+use crate::{game::*, helpers::PositionMapper, transposition_table::TranspositionTableHasher};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Game {
     /// White pieces.
     pub white: Bitboard,
@@ -22,7 +22,7 @@ pub struct Game {
     /// The current ply. One ply = one side's turn (half-move).
     pub ply: usize,
     /// Only needed for hashing so we can determine if a position has occured three times (draw).
-    transposition_table: TranspositionTable,
+    hasher: TranspositionTableHasher,
     pub move_history_hash: Vec<u64>,
     pub current_hash: u64,
 }
@@ -51,12 +51,33 @@ impl Game {
             side_to_move: Color::Black,
             move_history: Vec::new(),
             ply: 0,
-            transposition_table: TranspositionTable::new(0),
+            // The keys only have to tell the positions of one game apart.
+            // Fixed keys keep games, and everything that depends on their hashes, reproducible.
+            hasher: TranspositionTableHasher::with_seed(0),
             move_history_hash: Vec::new(),
             current_hash: 0,
         };
 
-        game.current_hash = game.transposition_table.hash(&game);
+        game.current_hash = game.hasher.hash(&game);
+
+        game
+    }
+
+    /// Create a game from an arbitrary position without any move history.
+    pub fn from_bitboards(
+        white: Bitboard,
+        black: Bitboard,
+        white_kings: Bitboard,
+        black_kings: Bitboard,
+        side_to_move: Color,
+    ) -> Self {
+        let mut game = Self::new();
+        game.white = white;
+        game.black = black;
+        game.white_kings = white_kings;
+        game.black_kings = black_kings;
+        game.side_to_move = side_to_move;
+        game.current_hash = game.hasher.hash(&game);
 
         game
     }
@@ -70,6 +91,19 @@ impl Game {
         self.is_white_win() || self.is_black_win() || self.is_draw()
     }
 
+    /// How the game ended, `None` while it is still going on.
+    pub fn result(&mut self) -> Option<GameResult> {
+        if self.is_draw() {
+            Some(GameResult::Draw)
+        } else if self.is_black_win() {
+            Some(GameResult::BlackWin)
+        } else if self.is_white_win() {
+            Some(GameResult::WhiteWin)
+        } else {
+            None
+        }
+    }
+
     pub fn is_black_win(&mut self) -> bool {
         // We might want to optimize this by not neccecarily generating all the moves
         if self.white.is_empty() {
@@ -103,7 +137,8 @@ impl Game {
             .iter()
             .rev()
             .position(|ms| ms.is_irreversible())
-            .expect("No irreversible move found");
+            // Games set up from a position might not have played an irreversible move yet.
+            .unwrap_or(self.move_history.len());
 
         if self
             .move_history_hash
@@ -112,7 +147,8 @@ impl Game {
             .take(first_irreversible + 1)
             .filter(|&&h| h == self.current_hash)
             .count()
-            >= 3
+            // The history does not hold the current position, so two earlier occurrences are enough.
+            >= 2
         {
             // println!(
             //     "Draw by repetition {:?}, {:?} ",
@@ -163,6 +199,96 @@ impl Game {
         Err("No valid move sequences found".to_string())
     }
 
+    /// Create a game from a position in FEN notation, e.g. `B:W21,22,K30:B1,2,K5`.
+    /// The first field is the side to move, followed by the white and black pieces.
+    /// Kings are prefixed with a `K`.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let mut fields = fen.trim().trim_end_matches('.').split(':');
+
+        let side_to_move = match fields.next() {
+            Some("B") => Color::Black,
+            Some("W") => Color::White,
+            _ => return Err("Invalid side to move".to_string()),
+        };
+
+        let mut white = Bitboard::EMPTY;
+        let mut black = Bitboard::EMPTY;
+        let mut white_kings = Bitboard::EMPTY;
+        let mut black_kings = Bitboard::EMPTY;
+
+        for field in fields {
+            let (pieces, kings) = match field.chars().next() {
+                Some('W') => (&mut white, &mut white_kings),
+                Some('B') => (&mut black, &mut black_kings),
+                _ => return Err("Invalid color".to_string()),
+            };
+
+            for square in field[1..].split(',').filter(|square| !square.is_empty()) {
+                let (is_king, square) = match square.strip_prefix('K') {
+                    Some(square) => (true, square),
+                    None => (false, square),
+                };
+
+                let position = square
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid square".to_string())?;
+                if !(1..=32).contains(&position) {
+                    return Err("Invalid square".to_string());
+                }
+
+                let index = PositionMapper::position_to_index[position];
+                pieces.set(index);
+                if is_king {
+                    kings.set(index);
+                }
+            }
+        }
+
+        if !(white & black).is_empty() {
+            return Err("Square occupied twice".to_string());
+        }
+
+        Ok(Self::from_bitboards(
+            white,
+            black,
+            white_kings,
+            black_kings,
+            side_to_move,
+        ))
+    }
+
+    /// The current position in FEN notation.
+    pub fn to_fen(&self) -> String {
+        let pieces_to_fen = |pieces: Bitboard, kings: Bitboard| {
+            let mut positions = pieces
+                .into_iter()
+                .map(|index| PositionMapper::index_to_position[index])
+                .collect::<Vec<usize>>();
+            positions.sort_unstable();
+
+            positions
+                .iter()
+                .map(
+                    |&position| match kings.get(PositionMapper::position_to_index[position]) {
+                        true => format!("K{}", position),
+                        false => position.to_string(),
+                    },
+                )
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+
+        format!(
+            "{}:W{}:B{}",
+            match self.side_to_move {
+                Color::White => "W",
+                Color::Black => "B",
+            },
+            pieces_to_fen(self.white, self.white_kings),
+            pieces_to_fen(self.black, self.black_kings)
+        )
+    }
+
     pub fn to_console_string(&mut self) -> String {
         let mut s = String::new();
 
@@ -180,7 +306,7 @@ impl Game {
                 Color::White => "White moves next ",
                 Color::Black => "Black moves next ",
             };
-            let mut e = Engine::new(self.side_to_move.clone(), Duration::from_secs(1));
+            let mut e = Engine::new(Duration::from_secs(1));
             s += &format!(
                 "<{}>",
                 match self.side_to_move {
@@ -267,9 +393,9 @@ impl Game {
         self.ply += 1;
         self.side_to_move.switch();
         self.move_history.push(moves_sequence.clone());
-        let hash =
-            self.transposition_table
-                .hash_move_sequence(self.current_hash, moves_sequence, true);
+        let hash = self
+            .hasher
+            .hash_move_sequence(self.current_hash, moves_sequence, true);
 
         // assert!(hash != self.current_hash);
         self.move_history_hash.push(self.current_hash);
@@ -1039,3 +1165,27 @@ pub mod perft_tests {
         assert_eq!(game.perft(12), 388_623_673);
     }
 }
+
+#[cfg(test)]
+mod game_tests {
+    use super::*;
+
+    #[test]
+    fn third_occurrence_of_a_position_is_a_draw() {
+        let mut game = Game::from_fen("W:WK18:BK1").unwrap();
+        let round = ["18-22", "1-5", "22-18", "5-1"];
+
+        for m in round {
+            game.make_move_external(m).unwrap();
+        }
+        // The second occurrence.
+        assert!(!game.is_draw());
+
+        for m in &round[..3] {
+            game.make_move_external(m).unwrap();
+            assert!(!game.is_draw());
+        }
+        game.make_move_external(round[3]).unwrap();
+        assert!(game.is_draw());
+    }
+}
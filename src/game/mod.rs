@@ -22,3 +22,11 @@ impl Color {
         }
     }
 }
+
+/// How a finished game ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameResult {
+    BlackWin,
+    WhiteWin,
+    Draw,
+}
@@ -102,6 +102,24 @@ impl MoveSequence {
         self.0[0].is_king_move
     }
 
+    /// The index of the tile the sequence starts from.
+    pub fn from(&self) -> usize {
+        self.0[0].from
+    }
+
+    /// The index of the tile the sequence ends on.
+    pub fn to(&self) -> usize {
+        self.0.last().unwrap().to
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.0
+    }
+
+    pub fn side_to_move(&self) -> &Color {
+        &self.0[0].side_to_move
+    }
+
     pub fn move_sequence_type(&self) -> MoveSequenceType {
         match self.is_capture() {
             true => MoveSequenceType::Jump,
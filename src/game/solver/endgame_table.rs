@@ -1,6 +1,11 @@
-use std::{collections::HashMap, fs, str::FromStr};
+use std::{
+    collections::HashMap,
+    fs,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
 
-use crate::game::Bitboard;
+use crate::game::{Bitboard, Game};
 
 #[derive(Debug)]
 pub struct EndgameTable {
@@ -18,7 +23,8 @@ impl EndgameTable {
     pub fn from_db(path: String) -> Result<Self, String> {
         // let mut table = HashMap::new();
 
-        let contents = fs::read_to_string(path).expect("Should have been able to read the file");
+        let contents = fs::read_to_string(&path)
+            .map_err(|error| format!("could not read {}: {}", path, error))?;
 
         let table = HashMap::from_iter(contents.lines().into_iter().filter_map(|line| {
             if line.starts_with("BASE") {
@@ -77,15 +83,76 @@ impl EndgameTable {
     pub fn fetch(&self, position: String) -> Option<EndgameTableFlag> {
         self.table.get(&position)?.clone().into()
     }
+
+    /// Look up a game position in the database.
+    /// The database only covers positions with at most six pieces
+    /// where neither side has a capture available.
+    pub fn probe(&self, game: &Game) -> Option<EndgameTableFlag> {
+        if game.white.is_empty() || game.black.is_empty() || (game.white | game.black).count() > 6 {
+            return None;
+        }
+
+        let w = game.generate_white_jumps(&Bitboard::ALL);
+        let b = game.generate_black_jumps(&Bitboard::ALL);
+        if (w.0 | w.1 | w.2 | w.3 | b.0 | b.1 | b.2 | b.3).count() != 0 {
+            return None;
+        }
+
+        let position = EndgameTable::state_to_string(
+            game.black,
+            game.white,
+            game.black_kings,
+            game.white_kings,
+        );
+
+        self.fetch(position)
+    }
+
+    /// All slices in the database together with their flag.
+    pub fn slices(&self) -> impl Iterator<Item = (&String, &EndgameTableFlag)> {
+        self.table.iter()
+    }
+
+    pub fn insert(&mut self, position: String, flag: EndgameTableFlag) {
+        self.table.insert(position, flag);
+    }
+}
+
+impl EndgameTable {
+    pub const DEFAULT_PATH: &'static str = "./DB6/DB6.idx";
+
+    /// The default database, loaded on the first call and shared by every caller afterwards.
+    pub fn shared() -> Arc<Self> {
+        Self::load_shared(Self::DEFAULT_PATH).expect("no database installed")
+    }
+
+    /// The database at `path`, loaded on the first call for the path and shared by every caller
+    /// afterwards. An empty path is an empty database.
+    pub fn load_shared(path: &str) -> Result<Arc<Self>, String> {
+        static SHARED: OnceLock<Mutex<HashMap<String, Arc<EndgameTable>>>> = OnceLock::new();
+
+        if path.is_empty() {
+            return Ok(Arc::new(Self::new()));
+        }
+
+        let mut shared = SHARED.get_or_init(Default::default).lock().unwrap();
+        if let Some(table) = shared.get(path) {
+            return Ok(table.clone());
+        }
+
+        let table = Arc::new(Self::from_db(path.to_string())?);
+        shared.insert(path.to_string(), table.clone());
+        Ok(table)
+    }
 }
 
 impl Default for EndgameTable {
     fn default() -> Self {
-        Self::from_db("./DB6/DB6.idx".to_string()).expect("no database installed")
+        Self::from_db(Self::DEFAULT_PATH.to_string()).expect("no database installed")
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EndgameTableFlag {
     Draw,
     BlackWin,
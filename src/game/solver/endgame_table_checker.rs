@@ -0,0 +1,498 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::*;
+
+/// Validates an endgame database against itself and the move generator.
+///
+/// Every position of a slice that is flagged as entirely won, lost or drawn has to agree with
+/// the minimax of its successors. Successors are valued with the database as well, positions
+/// that are not covered by it are expanded for a few more plies. Optionally the result is
+/// also cross checked with a shallow search of an `Engine` that does not use the database.
+pub struct EndgameTableChecker<'a> {
+    endgame_table: &'a EndgameTable,
+    /// The sides to move that are checked for every position.
+    pub sides: Vec<Color>,
+    /// Number of plies successors are expanded when they are not covered by the database.
+    pub resolve_depth: usize,
+    /// Engine used for the cross check, if any.
+    engine: Option<(Engine, usize)>,
+}
+
+/// A position whose database value does not match the checks.
+#[derive(Debug, Clone)]
+pub struct Inconsistency {
+    pub slice: String,
+    pub index: u64,
+    pub fen: String,
+    pub reason: String,
+}
+
+/// Result of checking one slice of the database.
+#[derive(Debug, Clone)]
+pub struct SliceReport {
+    pub slice: String,
+    pub flag: EndgameTableFlag,
+    /// Positions that have been checked.
+    pub checked: u64,
+    /// Positions that are not covered by the database because of pending captures.
+    pub skipped: u64,
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionCheck {
+    Consistent,
+    /// The position is not covered by the database.
+    Skipped,
+    Inconsistent(String),
+}
+
+impl<'a> EndgameTableChecker<'a> {
+    pub fn new(endgame_table: &'a EndgameTable) -> Self {
+        EndgameTableChecker {
+            endgame_table,
+            sides: vec![Color::Black, Color::White],
+            resolve_depth: 2,
+            engine: None,
+        }
+    }
+
+    /// Cross check every position with an engine search of the given depth.
+    /// The engine does not use the database, so only forced wins it finds are compared.
+    pub fn with_engine_depth(mut self, depth: usize) -> Self {
+        self.engine = match depth {
+            0 => None,
+            depth => Some((
+                Engine::with_endgame_table(Duration::MAX, EndgameTable::new()),
+                depth,
+            )),
+        };
+        self
+    }
+}
+
+/// Checking
+impl<'a> EndgameTableChecker<'a> {
+    /// Check every position of a slice.
+    pub fn check_slice(&mut self, slice: &str) -> Result<SliceReport, String> {
+        let slice_positions = SlicePositions::new(slice)?;
+        let mut report = self.create_report(slice)?;
+
+        for index in 0..slice_positions.size() {
+            if let Some(position) = slice_positions.position(index) {
+                self.check_indexed_position(&mut report, index, position);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Check a number of random positions of a slice.
+    pub fn sample_slice(&mut self, slice: &str, samples: usize) -> Result<SliceReport, String> {
+        let slice_positions = SlicePositions::new(slice)?;
+        let mut report = self.create_report(slice)?;
+        let mut rng = rand::thread_rng();
+
+        // Most indices are valid positions, so this terminates quickly.
+        let mut found = 0;
+        let mut attempts = 0;
+        while found < samples && attempts < samples * 1_000 {
+            attempts += 1;
+
+            let index = rng.gen_range(0..slice_positions.size());
+            if let Some(position) = slice_positions.position(index) {
+                self.check_indexed_position(&mut report, index, position);
+                found += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn create_report(&self, slice: &str) -> Result<SliceReport, String> {
+        let flag = self
+            .endgame_table
+            .fetch(slice.to_string())
+            .ok_or(format!("slice {} is not in the database", slice))?;
+
+        Ok(SliceReport {
+            slice: slice.to_string(),
+            flag,
+            checked: 0,
+            skipped: 0,
+            inconsistencies: vec![],
+        })
+    }
+
+    fn check_indexed_position(
+        &mut self,
+        report: &mut SliceReport,
+        index: u64,
+        (white, black, white_kings, black_kings): (Bitboard, Bitboard, Bitboard, Bitboard),
+    ) {
+        for side in self.sides.clone() {
+            let mut game = Game::from_bitboards(white, black, white_kings, black_kings, side);
+
+            match self.check_position(&mut game) {
+                PositionCheck::Consistent => report.checked += 1,
+                PositionCheck::Skipped => report.skipped += 1,
+                PositionCheck::Inconsistent(reason) => {
+                    report.checked += 1;
+                    report.inconsistencies.push(Inconsistency {
+                        slice: report.slice.clone(),
+                        index,
+                        fen: game.to_fen(),
+                        reason,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Check a single position against the minimax of its successors
+    /// and, if enabled, against the engine.
+    pub fn check_position(&mut self, game: &mut Game) -> PositionCheck {
+        let expected = match self
+            .endgame_table
+            .probe(game)
+            .and_then(|flag| EndgameTableChecker::flag_value(&flag, &game.side_to_move))
+        {
+            Some(expected) => expected,
+            None => return PositionCheck::Skipped,
+        };
+
+        let (lower, upper) = self.successor_bounds(game, self.resolve_depth);
+        if expected < lower || expected > upper {
+            return PositionCheck::Inconsistent(format!(
+                "database says {} but successors give {}..{}",
+                EndgameTableChecker::value_to_string(expected),
+                EndgameTableChecker::value_to_string(lower),
+                EndgameTableChecker::value_to_string(upper)
+            ));
+        }
+
+        if let Some((engine, depth)) = &mut self.engine {
+            let score = engine.search_to_depth(game, *depth);
+            let proven = if score > Score::WIN / 2 {
+                Some(1)
+            } else if score < -Score::WIN / 2 {
+                Some(-1)
+            } else {
+                None
+            };
+
+            if let Some(proven) = proven {
+                if proven != expected {
+                    return PositionCheck::Inconsistent(format!(
+                        "database says {} but the engine proves {} at depth {}",
+                        EndgameTableChecker::value_to_string(expected),
+                        EndgameTableChecker::value_to_string(proven),
+                        depth
+                    ));
+                }
+            }
+        }
+
+        PositionCheck::Consistent
+    }
+}
+
+/// Game theoretic values from the perspective of the side to move:
+/// `1` is a win, `0` a draw and `-1` a loss.
+impl<'a> EndgameTableChecker<'a> {
+    /// Lower and upper bound of the value of a position.
+    fn bounds(&self, game: &mut Game, depth: usize) -> (i32, i32) {
+        if game.generate_move_sequences().is_empty() {
+            return (-1, -1);
+        }
+
+        if let Some(value) = self
+            .endgame_table
+            .probe(game)
+            .and_then(|flag| EndgameTableChecker::flag_value(&flag, &game.side_to_move))
+        {
+            return (value, value);
+        }
+
+        if depth == 0 {
+            return (-1, 1);
+        }
+
+        self.successor_bounds(game, depth - 1)
+    }
+
+    /// Lower and upper bound of the value of a position, computed from its successors only.
+    fn successor_bounds(&self, game: &mut Game, depth: usize) -> (i32, i32) {
+        let mut lower = -1;
+        let mut upper = -1;
+
+        for m in game.generate_move_sequences() {
+            game.make_move_sequence(&m);
+            let (successor_lower, successor_upper) = self.bounds(game, depth);
+            game.unmake_move_sequence();
+
+            lower = lower.max(-successor_upper);
+            upper = upper.max(-successor_lower);
+
+            if lower == 1 {
+                break;
+            }
+        }
+
+        (lower, upper)
+    }
+
+    /// Value of a database flag for the side to move.
+    /// The "mostly" flags do not have an exact value.
+    fn flag_value(flag: &EndgameTableFlag, side_to_move: &Color) -> Option<i32> {
+        let value = match flag {
+            EndgameTableFlag::BlackWin => 1,
+            EndgameTableFlag::WhiteWin => -1,
+            EndgameTableFlag::Draw => return Some(0),
+            _ => return None,
+        };
+
+        match side_to_move {
+            Color::Black => Some(value),
+            Color::White => Some(-value),
+        }
+    }
+
+    fn value_to_string(value: i32) -> &'static str {
+        match value {
+            1 => "win",
+            0 => "draw",
+            _ => "loss",
+        }
+    }
+}
+
+/// Enumerates the positions of a database slice, e.g. `2011.53`.
+///
+/// The index of a position combines the placements of the black men, white men,
+/// black kings and white kings, in that order. Placements where pieces overlap
+/// or where no piece stands on the leading rank do not belong to the slice,
+/// so not every index is a valid position.
+#[derive(Debug)]
+pub struct SlicePositions {
+    slice: String,
+    black_kings: usize,
+    white_kings: usize,
+    black_men: usize,
+    white_men: usize,
+    black_rank: Bitboard,
+    white_rank: Bitboard,
+    black_men_squares: Vec<usize>,
+    white_men_squares: Vec<usize>,
+    black_kings_squares: Vec<usize>,
+    white_kings_squares: Vec<usize>,
+}
+
+impl SlicePositions {
+    /// Rows in the order used by `Bitboard::to_rank`.
+    const RANKS: [Bitboard; 8] = [
+        Bitboard::ONE,
+        Bitboard::TWO,
+        Bitboard::THREE,
+        Bitboard::FOUR,
+        Bitboard::FIVE,
+        Bitboard::SIX,
+        Bitboard::SEVEN,
+        Bitboard::EIGHT,
+    ];
+
+    pub fn new(slice: &str) -> Result<Self, String> {
+        let digits = slice
+            .chars()
+            .filter(|c| *c != '.')
+            .map(|c| c.to_digit(10).map(|d| d as usize))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or(format!("invalid slice {}", slice))?;
+
+        if digits.len() != 6 || digits[4] > 7 || digits[5] > 7 {
+            return Err(format!("invalid slice {}", slice));
+        }
+
+        // Pieces of a color can only stand on its leading rank or on the ranks after it.
+        let squares = |rank: usize, excluded: Bitboard| {
+            SlicePositions::RANKS[rank..]
+                .iter()
+                .fold(Bitboard::EMPTY, |squares, row| squares | *row)
+                & !excluded
+        };
+
+        // Men on the last row would have been promoted.
+        Ok(SlicePositions {
+            slice: slice.to_string(),
+            black_kings: digits[0],
+            white_kings: digits[1],
+            black_men: digits[2],
+            white_men: digits[3],
+            black_rank: SlicePositions::RANKS[digits[4]],
+            white_rank: SlicePositions::RANKS[digits[5]],
+            black_men_squares: squares(digits[4], Bitboard::ONE).collect(),
+            white_men_squares: squares(digits[5], Bitboard::EIGHT).collect(),
+            black_kings_squares: squares(digits[4], Bitboard::EMPTY).collect(),
+            white_kings_squares: squares(digits[5], Bitboard::EMPTY).collect(),
+        })
+    }
+
+    /// Number of indices of the slice.
+    pub fn size(&self) -> u64 {
+        self.dimensions().iter().product()
+    }
+
+    fn dimensions(&self) -> [u64; 4] {
+        [
+            binomial(self.black_men_squares.len(), self.black_men),
+            binomial(self.white_men_squares.len(), self.white_men),
+            binomial(self.black_kings_squares.len(), self.black_kings),
+            binomial(self.white_kings_squares.len(), self.white_kings),
+        ]
+    }
+
+    /// The position `(white, black, white kings, black kings)` at an index,
+    /// or `None` if the index does not describe a position of this slice.
+    pub fn position(&self, index: u64) -> Option<(Bitboard, Bitboard, Bitboard, Bitboard)> {
+        let [_, white_men_size, black_kings_size, white_kings_size] = self.dimensions();
+
+        let white_kings_index = index % white_kings_size;
+        let index = index / white_kings_size;
+        let black_kings_index = index % black_kings_size;
+        let index = index / black_kings_size;
+        let white_men_index = index % white_men_size;
+        let black_men_index = index / white_men_size;
+
+        let black_men = nth_combination(&self.black_men_squares, self.black_men, black_men_index);
+        let white_men = nth_combination(&self.white_men_squares, self.white_men, white_men_index);
+        let black_kings = nth_combination(
+            &self.black_kings_squares,
+            self.black_kings,
+            black_kings_index,
+        );
+        let white_kings = nth_combination(
+            &self.white_kings_squares,
+            self.white_kings,
+            white_kings_index,
+        );
+
+        let pieces = [black_men, white_men, black_kings, white_kings];
+        for (i, a) in pieces.iter().enumerate() {
+            for b in pieces.iter().skip(i + 1) {
+                if !(*a & *b).is_empty() {
+                    return None;
+                }
+            }
+        }
+
+        let black = black_men | black_kings;
+        let white = white_men | white_kings;
+        if (black & self.black_rank).is_empty() || (white & self.white_rank).is_empty() {
+            return None;
+        }
+
+        debug_assert_eq!(
+            EndgameTable::state_to_string(black, white, black_kings, white_kings),
+            self.slice
+        );
+
+        Some((white, black, white_kings, black_kings))
+    }
+}
+
+fn binomial(n: usize, k: usize) -> u64 {
+    if k > n {
+        return 0;
+    }
+
+    (0..k).fold(1, |result, i| result * (n - i) as u64 / (i + 1) as u64)
+}
+
+/// The combination of `k` squares at `index` in lexicographic order.
+fn nth_combination(squares: &[usize], k: usize, mut index: u64) -> Bitboard {
+    let mut combination = Bitboard::EMPTY;
+    let mut start = 0;
+
+    for remaining in (1..=k).rev() {
+        for (i, square) in squares.iter().enumerate().skip(start) {
+            let count = binomial(squares.len() - i - 1, remaining - 1);
+            if index < count {
+                combination.set(*square);
+                start = i + 1;
+                break;
+            }
+            index -= count;
+        }
+    }
+
+    combination
+}
+
+#[cfg(test)]
+mod endgame_table_checker_tests {
+    use super::*;
+
+    #[test]
+    fn slice_enumerates_every_position_once() {
+        let slice_positions = SlicePositions::new("1011.65").unwrap();
+        let positions = (0..slice_positions.size())
+            .filter_map(|index| slice_positions.position(index))
+            .collect::<Vec<_>>();
+
+        // Brute force all placements of one black king, one black man and one white man.
+        let mut expected = 0;
+        for black_king in Bitboard::ALL {
+            for black_man in Bitboard::ALL & !Bitboard::ONE {
+                for white_man in Bitboard::ALL & !Bitboard::EIGHT {
+                    if black_king == black_man || black_king == white_man || black_man == white_man
+                    {
+                        continue;
+                    }
+
+                    let black =
+                        Bitboard::create_one_hot(black_king) | Bitboard::create_one_hot(black_man);
+                    let white = Bitboard::create_one_hot(white_man);
+                    let black_kings = Bitboard::create_one_hot(black_king);
+                    if EndgameTable::state_to_string(black, white, black_kings, Bitboard::EMPTY)
+                        == "1011.65"
+                    {
+                        expected += 1;
+                    }
+                }
+            }
+        }
+
+        assert_eq!(positions.len(), expected);
+        for (i, a) in positions.iter().enumerate() {
+            assert!(positions.iter().skip(i + 1).all(|b| a != b));
+        }
+    }
+
+    #[test]
+    fn finds_wrong_database_value() {
+        // Black to move is blocked and loses immediately.
+        let mut game = Game::from_fen("B:W32:B28").unwrap();
+        assert!(game.generate_move_sequences().is_empty());
+
+        let position = EndgameTable::state_to_string(
+            game.black,
+            game.white,
+            game.black_kings,
+            game.white_kings,
+        );
+
+        let mut endgame_table = EndgameTable::new();
+        endgame_table.insert(position.clone(), EndgameTableFlag::BlackWin);
+        let mut checker = EndgameTableChecker::new(&endgame_table);
+        assert!(matches!(
+            checker.check_position(&mut game),
+            PositionCheck::Inconsistent(_)
+        ));
+
+        endgame_table.insert(position, EndgameTableFlag::WhiteWin);
+        let mut checker = EndgameTableChecker::new(&endgame_table).with_engine_depth(2);
+        assert_eq!(checker.check_position(&mut game), PositionCheck::Consistent);
+    }
+}
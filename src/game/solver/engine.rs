@@ -1,7 +1,13 @@
 #![allow(unused)]
 
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
 use super::*;
 use crate::transposition_table::{
     TranspositionTable, TranspositionTableEntry, TranspositionTableFlag,
@@ -9,209 +15,1070 @@ use crate::transposition_table::{
 
 use crate::game::solver::endgame_table::EndgameTable;
 
-const CHECK_EVERY_N_NODES: usize = 4_096;
+/// Half width of the first aspiration window around the previous iteration's score.
+const ASPIRATION_WINDOW: i32 = 100;
+/// Margins by remaining depth from 1, for how much a quiet move can improve the evaluation.
+const FUTILITY_MARGINS: [i32; 2] = [200, 500];
+/// Number of moves that are searched to the full depth before late move reductions kick in.
+const LATE_MOVE_REDUCTION_MOVES: usize = 3;
+/// Minimum remaining depth for late move reductions.
+const LATE_MOVE_REDUCTION_DEPTH: usize = 3;
+/// Speed time limits are converted to node limits at in a deterministic search,
+/// about the speed of a single thread.
+const DETERMINISTIC_NODES_PER_SECOND: f64 = 1_000_000.0;
+
+/// Record an event of the search tree, if the engine is tracing.
+/// Without the `trace` feature this expands to nothing.
+macro_rules! trace {
+    ($engine:expr, $event:ident($($argument:expr),*)) => {
+        #[cfg(feature = "trace")]
+        if let Some(tracer) = &$engine.tracer {
+            tracer.lock().unwrap().$event($($argument),*);
+        }
+    };
+}
 
 #[derive(Debug)]
 pub struct Engine {
-    pub color: Color,
+    pub config: EngineConfig,
     pub current_depth: usize,
+    /// Time for a move in `find_best_move`.
     pub max_time: Duration,
-    start_time: std::time::Instant,
+    limits: SearchLimits,
+    time_manager: TimeManager,
     stopped_searching: bool,
+    /// Tells all threads of a search to stop.
+    stop_signal: Arc<AtomicBool>,
+    /// Threads of searches whose future was dropped. They were told to stop,
+    /// and are joined before the next search clears the stop signal.
+    dropped_searches: Arc<Mutex<Vec<std::thread::JoinHandle<()>>>>,
+    /// `0` for the main search thread, helper threads are numbered from `1`.
+    thread_id: usize,
     pub best_move: Option<MoveSequence>,
     pub best_score: i32,
+    /// The best `config.multi_pv` moves of the last finished iteration, best first,
+    /// with their scores from the perspective of the side to move and their principal variations.
+    pub principal_variations: Vec<(MoveSequence, i32, String)>,
+    /// Root moves left out of the current pass of a multi-PV search.
+    excluded_moves: Vec<MoveSequence>,
+    /// Deepest ply from the root reached in the current iteration.
+    selective_depth: usize,
+    /// Number of scores found by the search that depend on more than the position: repetitions,
+    /// which depend on the path, and draws valued with contempt, which depend on the root.
+    /// Tells whether a score below a node depended on one.
+    path_dependent_scores: usize,
+    /// Plies added by extensions on the path from the root to the current node.
+    extensions: usize,
+    /// Score of a draw for the side to move at the root, from `config.draw_policy`.
+    root_draw_score: i32,
+    /// Seed of the evaluation noise and the move choice of a weaker `config.strength`,
+    /// new for every search.
+    strength_seed: u64,
+    /// Receives the progress of the search, only the main search thread sends it.
+    search_info: Option<tokio::sync::mpsc::UnboundedSender<SearchInfo>>,
+    /// Number of nodes searched during the last search, summed over all threads.
     pub searched_nodes: usize,
-    transposition_table: TranspositionTable,
-    endgame_lookup_table: EndgameTable,
+    /// Nodes searched so far by all threads of the running search, for the node limit.
+    shared_nodes: Arc<AtomicUsize>,
+    /// Number of positions resolved by the endgame database during the last search.
+    pub db_hits: usize,
+    /// Duration of the last search.
+    pub search_time: Duration,
+    /// The ply of the game the current search started from.
+    root_ply: usize,
+    move_ordering: MoveOrdering,
+    transposition_table: Arc<TranspositionTable>,
+    endgame_lookup_table: Arc<EndgameTable>,
+    /// Whether the last search continued a ponder search.
+    pub ponder_hit: bool,
+    ponder: Option<Ponder>,
+    /// Writes the search tree of the main search thread to a file.
+    #[cfg(feature = "trace")]
+    tracer: Option<Arc<std::sync::Mutex<SearchTracer>>>,
+}
+
+/// The answer of `Engine::find_best_move`, for every position.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineMove {
+    /// The game is already over, there is nothing to play.
+    GameOver(GameResult),
+    /// The only legal move, returned right away without a search.
+    ForcedMove(MoveSequence),
+    /// The best move of the search, its score from the perspective of white
+    /// and the principal variation.
+    BestMove {
+        best_move: MoveSequence,
+        score: i32,
+        principal_variation: String,
+    },
+}
+
+impl EngineMove {
+    /// The move to play, `None` if the game is over.
+    pub fn best_move(&self) -> Option<&MoveSequence> {
+        match self {
+            EngineMove::GameOver(_) => None,
+            EngineMove::ForcedMove(m) => Some(m),
+            EngineMove::BestMove { best_move, .. } => Some(best_move),
+        }
+    }
+}
+
+/// Stops the running search of an engine, from another thread or task.
+/// The search returns the best move it has found so far.
+#[derive(Debug, Clone)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Stops the search when dropped, so cancelling a search future does not leave it running.
+struct StopOnDrop(StopHandle);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.stop();
+    }
+}
+
+/// A search running on a dedicated thread. The engine is handed back when the search is done.
+#[derive(Debug)]
+struct BackgroundSearch {
+    result: tokio::sync::oneshot::Receiver<Engine>,
+    /// `None` once the thread is joined.
+    thread: Option<std::thread::JoinHandle<()>>,
+    dropped_searches: Arc<Mutex<Vec<std::thread::JoinHandle<()>>>>,
+}
+
+impl BackgroundSearch {
+    /// Run a search that is already started on a new thread.
+    fn spawn(mut engine: Engine, mut game: Game) -> Self {
+        let (sender, result) = tokio::sync::oneshot::channel();
+        let dropped_searches = engine.dropped_searches.clone();
+
+        let thread = std::thread::spawn(move || {
+            engine.run_started_search(&mut game);
+            // Nobody is waiting if the search was cancelled.
+            let _ = sender.send(engine);
+        });
+
+        BackgroundSearch {
+            result,
+            thread: Some(thread),
+            dropped_searches,
+        }
+    }
+
+    /// Wait for the search to finish on its own.
+    async fn finish(mut self) -> Engine {
+        let engine = (&mut self.result).await.unwrap();
+        self.join();
+        engine
+    }
+
+    /// Wait for the search to finish, and stop it once `time` is up.
+    async fn finish_within(mut self, time: Duration, stop_handle: &StopHandle) -> Engine {
+        if tokio::time::timeout(time, &mut self.result).await.is_err() {
+            stop_handle.stop();
+        }
+
+        self.finish().await
+    }
+
+    /// Wait for a search that was told to stop, without an async runtime.
+    fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+impl Drop for BackgroundSearch {
+    /// A search dropped before it was joined is handed to the engine, so the next search
+    /// waits for it instead of running alongside it.
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.dropped_searches.lock().unwrap().push(thread);
+        }
+    }
+}
+
+/// A search running on the opponent's time, in the position after the expected reply.
+#[derive(Debug)]
+struct Ponder {
+    game: Game,
+    expected_move: MoveSequence,
+    search: BackgroundSearch,
+}
+
+impl Ponder {
+    /// Whether the opponent played the expected move, so the search is in the right position.
+    fn is_hit(&self, game: &Game) -> bool {
+        self.game.white == game.white
+            && self.game.black == game.black
+            && self.game.white_kings == game.white_kings
+            && self.game.black_kings == game.black_kings
+            && self.game.side_to_move == game.side_to_move
+    }
 }
 
 impl Engine {
-    pub fn new(color: Color, max_time: Duration) -> Self {
+    /// Create an engine with the default endgame database, which is loaded once
+    /// and shared by all engines, see `EndgameTable::shared`.
+    pub fn new(max_time: Duration) -> Self {
+        Engine::with_endgame_table(max_time, EndgameTable::shared())
+    }
+
+    /// Create an engine with the given endgame database instead of loading the default one.
+    /// Pass an `Arc<EndgameTable>` to share one database between engines.
+    /// The default `config.endgame_table_path` is kept, it does not name the given database.
+    pub fn with_endgame_table(
+        max_time: Duration,
+        endgame_lookup_table: impl Into<Arc<EndgameTable>>,
+    ) -> Self {
+        Engine::create(
+            max_time,
+            EngineConfig::default(),
+            endgame_lookup_table.into(),
+        )
+    }
+
+    /// Create an engine with the given options, see `EngineConfig::builder`.
+    /// The endgame database of `config.endgame_table_path` is shared with the other engines
+    /// that use the same path.
+    pub fn with_config(max_time: Duration, config: EngineConfig) -> Result<Self, String> {
+        let endgame_lookup_table = EndgameTable::load_shared(&config.endgame_table_path)?;
+
+        Ok(Engine::create(max_time, config, endgame_lookup_table))
+    }
+
+    fn create(
+        max_time: Duration,
+        config: EngineConfig,
+        endgame_lookup_table: Arc<EndgameTable>,
+    ) -> Self {
+        let transposition_table = TranspositionTable::new(config.transposition_table_size);
+
         Engine {
-            color,
+            config,
             current_depth: 0,
             max_time,
-            start_time: std::time::Instant::now(),
+            limits: SearchLimits::default(),
+            time_manager: TimeManager::new(&SearchLimits::default()),
             stopped_searching: false,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            dropped_searches: Arc::new(Mutex::new(vec![])),
+            thread_id: 0,
             best_move: None,
             best_score: Score::DRAW,
+            principal_variations: vec![],
+            excluded_moves: vec![],
+            selective_depth: 0,
+            path_dependent_scores: 0,
+            extensions: 0,
+            root_draw_score: Score::DRAW,
+            strength_seed: 0,
+            search_info: None,
             searched_nodes: 0,
-            transposition_table: TranspositionTable::default(),
-            endgame_lookup_table: EndgameTable::default(),
+            shared_nodes: Arc::new(AtomicUsize::new(0)),
+            db_hits: 0,
+            search_time: Duration::ZERO,
+            root_ply: 0,
+            move_ordering: MoveOrdering::default(),
+            transposition_table: Arc::new(transposition_table),
+            endgame_lookup_table,
+            ponder_hit: false,
+            ponder: None,
+            #[cfg(feature = "trace")]
+            tracer: None,
+        }
+    }
+
+    /// Create a helper for a search that is already started.
+    /// It shares the transposition table, the endgame database, the stop signal and the node count.
+    fn create_helper(&self, thread_id: usize) -> Self {
+        Engine {
+            config: self.config.clone(),
+            current_depth: 0,
+            max_time: self.max_time,
+            limits: self.limits.clone(),
+            time_manager: self.time_manager.clone(),
+            stopped_searching: false,
+            stop_signal: self.stop_signal.clone(),
+            dropped_searches: self.dropped_searches.clone(),
+            thread_id,
+            best_move: None,
+            best_score: Score::DRAW,
+            principal_variations: vec![],
+            excluded_moves: vec![],
+            selective_depth: 0,
+            path_dependent_scores: 0,
+            extensions: 0,
+            root_draw_score: self.root_draw_score,
+            strength_seed: self.strength_seed,
+            search_info: match thread_id {
+                0 => self.search_info.clone(),
+                _ => None,
+            },
+            searched_nodes: 0,
+            shared_nodes: self.shared_nodes.clone(),
+            db_hits: 0,
+            search_time: Duration::ZERO,
+            root_ply: self.root_ply,
+            move_ordering: self.move_ordering.clone(),
+            transposition_table: self.transposition_table.clone(),
+            endgame_lookup_table: self.endgame_lookup_table.clone(),
+            ponder_hit: false,
+            ponder: None,
+            #[cfg(feature = "trace")]
+            tracer: match thread_id {
+                0 => self.tracer.clone(),
+                _ => None,
+            },
         }
     }
 }
 
 impl Engine {
-    pub async fn find_best_move(&mut self, game: &mut Game) -> (Option<MoveSequence>, i32, String) {
-        if game.side_to_move != self.color {
-            panic!("Engine is not playing as the side to move.");
+    /// Search for the best move for at most `max_time`, see `find_best_move_with_limits`.
+    pub fn find_best_move<'a>(
+        &'a mut self,
+        game: &'a mut Game,
+    ) -> impl Future<Output = EngineMove> + 'a {
+        let limits = SearchLimits::move_time(self.max_time);
+        self.find_best_move_with_limits(game, limits)
+    }
+
+    /// Search for the best move on a dedicated thread, within the given limits.
+    /// The search can be stopped early with a `StopHandle`, dropping the future stops it as well.
+    /// A stop from the moment this is called on applies, even before the future is polled.
+    /// Finished games and positions with a single legal move are answered without a search.
+    pub fn find_best_move_with_limits<'a>(
+        &'a mut self,
+        game: &'a mut Game,
+        limits: SearchLimits,
+    ) -> impl Future<Output = EngineMove> + 'a {
+        let limits = self.search_limits(limits);
+        // A ponder search can only be continued for a time, not to a depth or node count.
+        let ponder_time = TimeManager::new(&limits).optimum_time();
+        let is_hit = self
+            .ponder
+            .as_ref()
+            .is_some_and(|ponder| ponder.is_hit(game));
+        if !is_hit || ponder_time.is_none() {
+            self.stop_pondering();
         }
+        // Reset here and not once the future runs, so an early stop is not lost.
+        self.join_dropped_searches();
+        self.stop_signal.store(false, Ordering::Relaxed);
 
-        // Iterative deepening.
-        self.start_time = std::time::Instant::now();
-        self.searched_nodes = 0;
-        self.current_depth = 0;
-        self.stopped_searching = false;
+        self.finish_best_move(game, limits, ponder_time)
+    }
 
-        loop {
-            self.current_depth += 1;
+    /// The part of `find_best_move_with_limits` that runs once the future is polled.
+    async fn finish_best_move(
+        &mut self,
+        game: &mut Game,
+        limits: SearchLimits,
+        ponder_time: Option<Duration>,
+    ) -> EngineMove {
+        self.ponder_hit = false;
+        if let Some(result) = game.result() {
+            self.answer_without_search(None);
+            return EngineMove::GameOver(result);
+        }
 
-            self.search_root(game, self.current_depth, -Score::INFINITY, Score::INFINITY);
+        let mut moves = game.generate_move_sequences();
+        if moves.len() == 1 {
+            self.answer_without_search(moves.pop());
+            return EngineMove::ForcedMove(self.best_move.clone().unwrap());
+        }
 
-            let current_time = std::time::Instant::now();
-            let elapsed_time = current_time - self.start_time;
+        let stop_on_drop = StopOnDrop(self.stop_handle());
 
-            // We might also need to check if the time left is greater than 2* the time it took to
-            // search the previous depth
-            if elapsed_time >= self.max_time || self.current_depth >= 64 || self.stopped_searching {
-                // Hard stop
-                break;
+        // Only a ponder hit is still pondering.
+        let engine = match (self.ponder.take(), ponder_time) {
+            (Some(ponder), Some(ponder_time)) => {
+                self.ponder_hit = true;
+                ponder
+                    .search
+                    .finish_within(ponder_time, &stop_on_drop.0)
+                    .await
             }
+            _ => self.spawn_search(game, limits).finish().await,
+        };
+        self.take_over_search(engine);
 
-            // let time_left = self.max_time - elapsed_time;
+        // Stopped before the first iteration was done.
+        let best_move = self.best_move.get_or_insert(moves.swap_remove(0)).clone();
 
-            if self.max_time < elapsed_time {
-                // Soft stop
-                break;
-            }
-        }
-
-        let principal_variation_line = self
-            .transposition_table
-            .get_principal_variation_line(self.transposition_table.hash(game))
+        let principal_variation = self
+            .best_move_line(game)
             .iter()
             .map(|x| x.to_string())
             .collect::<Vec<String>>()
             .join(" ");
 
-        (
-            self.best_move.clone(),
-            match self.color {
+        EngineMove::BestMove {
+            best_move,
+            score: match game.side_to_move {
                 Color::Black => -self.best_score,
                 Color::White => self.best_score,
             },
-            principal_variation_line,
-        )
+            principal_variation,
+        }
+    }
+
+    /// Replace the result of the last search for a position that is answered without one.
+    fn answer_without_search(&mut self, best_move: Option<MoveSequence>) {
+        self.stop_pondering();
+        self.best_move = best_move;
+        self.best_score = Score::DRAW;
+        self.principal_variations.clear();
+        self.current_depth = 0;
+        self.searched_nodes = 0;
+        self.db_hits = 0;
+        self.search_time = Duration::ZERO;
+    }
+
+    /// The principal variation of the best move, as stored in the transposition table.
+    /// A weaker `config.strength` may play another move than the one the table leads to.
+    fn best_move_line(&self, game: &mut Game) -> Vec<MoveSequence> {
+        let line = self.transposition_table.get_principal_variation_line(game);
+
+        match &self.best_move {
+            Some(m) if line.first() != Some(m) => {
+                game.make_move_sequence(m);
+                let line = self.transposition_table.get_principal_variation_line(game);
+                game.unmake_move_sequence();
+
+                std::iter::once(m.clone()).chain(line).collect()
+            }
+            _ => line,
+        }
+    }
+
+    /// A handle to stop the running search, including a ponder search.
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(self.stop_signal.clone())
+    }
+
+    /// Start a search with the state of this engine on a dedicated thread.
+    fn spawn_search(&mut self, game: &Game, limits: SearchLimits) -> BackgroundSearch {
+        self.start_search(game, limits);
+
+        BackgroundSearch::spawn(self.create_helper(0), game.clone())
+    }
+
+    /// Take over the result and the move ordering of a finished background search.
+    fn take_over_search(&mut self, mut engine: Engine) {
+        self.best_move = engine.best_move.take();
+        self.best_score = engine.best_score;
+        self.principal_variations = std::mem::take(&mut engine.principal_variations);
+        self.current_depth = engine.current_depth;
+        self.searched_nodes = engine.searched_nodes;
+        self.db_hits = engine.db_hits;
+        self.search_time = engine.search_time;
+        self.root_ply = engine.root_ply;
+        self.move_ordering = std::mem::take(&mut engine.move_ordering);
+    }
+
+    /// Start searching on the opponent's time, `game` is the position after the engine's move.
+    /// The search assumes the opponent plays the reply predicted by the principal variation,
+    /// which is returned. If there is no predicted reply, no search is started.
+    ///
+    /// The next call to `find_best_move` continues the search for the optimum time of its limits
+    /// if the opponent played the predicted reply, and starts a fresh search otherwise.
+    pub fn start_pondering(&mut self, game: &Game) -> Option<MoveSequence> {
+        self.stop_pondering();
+        self.join_dropped_searches();
+        self.stop_signal.store(false, Ordering::Relaxed);
+
+        let mut game = game.clone();
+        let expected_move = self
+            .transposition_table
+            .get_principal_variation_line(&mut game)
+            .into_iter()
+            .next()?;
+        game.make_move_sequence(&expected_move);
+
+        if game.is_black_win() || game.is_white_win() || game.is_draw() {
+            return None;
+        }
+
+        let search = self.spawn_search(&game, SearchLimits::infinite());
+        self.ponder = Some(Ponder {
+            game,
+            expected_move: expected_move.clone(),
+            search,
+        });
+
+        Some(expected_move)
+    }
+
+    /// The reply the running ponder search expects.
+    pub fn ponder_move(&self) -> Option<&MoveSequence> {
+        self.ponder.as_ref().map(|ponder| &ponder.expected_move)
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.is_some()
+    }
+
+    /// Stop the ponder search and discard its result.
+    pub fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            self.stop_handle().stop();
+            ponder.search.join();
+        }
+    }
+
+    /// Wait for the searches of dropped `find_best_move` futures. The stop signal is still set
+    /// for them, as it is only cleared after this.
+    fn join_dropped_searches(&mut self) {
+        let threads = std::mem::take(&mut *self.dropped_searches.lock().unwrap());
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Prepare for a new game: stop pondering, forget the last search and the move ordering
+    /// history, and age the transposition table so entries of the old game are replaced first.
+    /// The engine analyses whichever side is to move, so it can play any number of games
+    /// with either colour.
+    pub fn new_game(&mut self) {
+        self.stop_pondering();
+        self.best_move = None;
+        self.best_score = Score::DRAW;
+        self.principal_variations.clear();
+        self.current_depth = 0;
+        self.move_ordering = MoveOrdering::default();
+        self.transposition_table.new_search();
+    }
+
+    /// The endgame database of the engine, to share it with other engines.
+    pub fn endgame_table(&self) -> Arc<EndgameTable> {
+        self.endgame_lookup_table.clone()
+    }
+
+    /// Receive the progress of the following searches, until the receiver is dropped or
+    /// this is called again.
+    pub fn subscribe_search_info(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<SearchInfo> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.search_info = Some(sender);
+        receiver
+    }
+
+    /// Nodes per second of the last search.
+    pub fn nodes_per_second(&self) -> usize {
+        (self.searched_nodes as f64 / self.search_time.as_secs_f64().max(1e-3)) as usize
+    }
+}
+
+impl Solver for Engine {
+    fn name(&self) -> &str {
+        "engine"
+    }
+
+    /// Search on the calling thread.
+    /// Empty limits search for `max_time`. Like `find_best_move`, finished games and positions
+    /// with a single legal move are answered without a search.
+    fn solve(&mut self, game: &Game, limits: &SearchLimits) -> SolverResult {
+        let mut game = game.clone();
+
+        let mut moves = game.generate_move_sequences();
+        let answer = match game.result() {
+            // The side to move has no move left, or the game is drawn.
+            Some(GameResult::Draw) => Some((None, Score::DRAW)),
+            Some(_) => Some((None, -Score::WIN)),
+            None if moves.len() == 1 => Some((moves.pop(), Score::DRAW)),
+            None => None,
+        };
+        if let Some((best_move, score)) = answer {
+            self.answer_without_search(best_move);
+
+            return SolverResult {
+                best_move: self.best_move.clone(),
+                score,
+                principal_variation: self.best_move.iter().cloned().collect(),
+                nodes: 0,
+            };
+        }
+        let limits = match *limits == SearchLimits::default() {
+            true => SearchLimits::move_time(self.max_time),
+            false => limits.clone(),
+        };
+
+        let score = self.search_with_limits(&mut game, limits);
+        // Stopped before the first iteration was done.
+        if self.best_move.is_none() {
+            self.best_move = game.generate_move_sequences().into_iter().next();
+        }
+
+        SolverResult {
+            best_move: self.best_move.clone(),
+            score,
+            principal_variation: self.best_move_line(&mut game),
+            nodes: self.searched_nodes,
+        }
     }
 }
 
 impl Engine {
-    fn search_root(&mut self, game: &mut Game, depth: usize, mut alpha: i32, mut beta: i32) -> i32 {
-        if game.is_black_win() {
-            return match game.side_to_move {
-                Color::Black => Score::WIN - game.ply as i32,
-                Color::White => -Score::WIN + game.ply as i32,
+    /// Search the position up to a fixed depth.
+    /// The search still stops once `max_time` is exceeded.
+    /// Returns the score from the perspective of the side to move.
+    pub fn search_to_depth(&mut self, game: &mut Game, depth: usize) -> i32 {
+        let limits = SearchLimits {
+            depth: Some(depth),
+            move_time: Some(self.max_time),
+            ..Default::default()
+        };
+
+        self.search_with_limits(game, limits)
+    }
+
+    /// Search the position on the calling thread.
+    /// Returns the score from the perspective of the side to move.
+    pub fn search_with_limits(&mut self, game: &mut Game, limits: SearchLimits) -> i32 {
+        self.stop_pondering();
+        self.join_dropped_searches();
+        self.stop_signal.store(false, Ordering::Relaxed);
+        self.start_search(game, limits);
+        self.run_started_search(game)
+    }
+
+    /// Search the position with `config.threads` threads (Lazy SMP).
+    /// Helper threads run their own iterative deepening on a copy of the game and
+    /// share the transposition table, the result of the main thread is used.
+    fn run_started_search(&mut self, game: &mut Game) -> i32 {
+        let max_depth = self
+            .limits
+            .depth
+            .unwrap_or(self.config.max_depth)
+            .min(self.config.max_depth);
+        let threads = if self.config.deterministic {
+            1
+        } else {
+            self.config.threads
+        };
+        let mut helpers = (1..threads)
+            .map(|thread_id| self.create_helper(thread_id))
+            .collect::<Vec<Engine>>();
+
+        let score = std::thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                let mut game = game.clone();
+                scope.spawn(move || helper.iterative_deepening(&mut game, max_depth));
+            }
+
+            let score = self.iterative_deepening(game, max_depth);
+
+            // The helpers keep searching until the main thread is done.
+            self.stop_signal.store(true, Ordering::Relaxed);
+
+            score
+        });
+
+        for helper in helpers {
+            self.searched_nodes += helper.searched_nodes;
+            self.db_hits += helper.db_hits;
+        }
+
+        let score = self.choose_weaker_move(score);
+
+        self.search_time = self.time_manager.elapsed();
+        trace!(self, finish());
+
+        score
+    }
+
+    /// Play the second best move instead of the best one, by the chance and within the margin
+    /// of `config.strength`. Returns the score of the move that is played.
+    fn choose_weaker_move(&mut self, score: i32) -> i32 {
+        let strength = &self.config.strength;
+        let mut rng = StdRng::seed_from_u64(self.strength_seed);
+
+        match self.principal_variations.get(..2) {
+            Some([(_, best_score, _), (m, second_score, _)])
+                if best_score - second_score <= strength.second_best_margin
+                    && rng.gen_range(0..100) < strength.second_best_chance =>
+            {
+                self.best_move = Some(m.clone());
+                self.best_score = *second_score;
+                *second_score
+            }
+            _ => score,
+        }
+    }
+
+    /// The limits a search actually uses. A deterministic search can not depend on the clock,
+    /// so its time limits are replaced by the number of nodes that fit into them.
+    /// The depth and node limits of a weaker `config.strength` apply to every search.
+    fn search_limits(&self, limits: SearchLimits) -> SearchLimits {
+        let limits = SearchLimits {
+            depth: Self::min_limit(limits.depth, self.config.strength.max_depth),
+            nodes: Self::min_limit(limits.nodes, self.config.strength.max_nodes),
+            ..limits
+        };
+
+        if !self.config.deterministic {
+            return limits;
+        }
+
+        let time_nodes = TimeManager::new(&limits)
+            .maximum_time()
+            .map(|time| (time.as_secs_f64() * DETERMINISTIC_NODES_PER_SECOND) as usize);
+
+        SearchLimits {
+            nodes: Self::min_limit(limits.nodes, time_nodes),
+            move_time: None,
+            clock: None,
+            ..limits
+        }
+    }
+
+    fn min_limit(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Reset the state of the previous search.
+    fn start_search(&mut self, game: &Game, limits: SearchLimits) {
+        let limits = self.search_limits(limits);
+        self.time_manager = TimeManager::new(&limits);
+        self.limits = limits;
+        self.best_move = None;
+        self.principal_variations.clear();
+        self.searched_nodes = 0;
+        self.shared_nodes.store(0, Ordering::Relaxed);
+        self.db_hits = 0;
+        self.current_depth = 0;
+        self.stopped_searching = false;
+        self.root_ply = game.ply;
+        self.root_draw_score = self.root_draw_score(game);
+        self.move_ordering.age();
+        self.strength_seed = match self.config.deterministic {
+            true => self.config.seed,
+            false => rand::random(),
+        };
+
+        let seed = self.config.deterministic.then_some(self.config.seed);
+        if self.transposition_table.size_in_megabytes() != self.config.transposition_table_size
+            || self.transposition_table.seed() != seed
+        {
+            let size = self.config.transposition_table_size;
+            self.transposition_table = Arc::new(match seed {
+                Some(seed) => TranspositionTable::with_seed(size, seed),
+                None => TranspositionTable::new(size),
+            });
+        }
+        self.transposition_table.new_search();
+        trace!(self, start_search(game, &self.limits));
+    }
+
+    fn iterative_deepening(&mut self, game: &mut Game, max_depth: usize) -> i32 {
+        let mut score = Score::DRAW;
+
+        // Every other helper thread starts one ply deeper,
+        // so the threads are not all searching the same depth.
+        self.current_depth = self.thread_id % 2;
+
+        while self.current_depth < max_depth {
+            self.current_depth += 1;
+            self.selective_depth = 0;
+            trace!(self, iteration(self.current_depth));
+            let previous_best_move = self.best_move.clone();
+            let depth_score = if self.thread_id == 0 {
+                self.multi_principal_variation_search(game, self.current_depth)
+            } else {
+                self.aspiration_search(game, self.current_depth, score)
             };
-        } else if game.is_white_win() {
-            return match game.side_to_move {
-                Color::Black => -Score::WIN + game.ply as i32,
-                Color::White => Score::WIN - game.ply as i32,
+
+            if self.stopped_searching {
+                break;
+            }
+
+            let is_first_iteration = previous_best_move.is_none();
+            let best_move_changed = !is_first_iteration && self.best_move != previous_best_move;
+            let score_drop = if is_first_iteration {
+                0
+            } else {
+                score - depth_score
             };
-        } else if game.is_draw() {
-            return Score::DRAW;
+            score = depth_score;
+
+            if self.is_time_up() {
+                break;
+            }
+
+            // Helper threads search until the main thread is done.
+            if self.thread_id == 0
+                && !self
+                    .time_manager
+                    .start_next_iteration(best_move_changed, score_drop)
+            {
+                break;
+            }
         }
 
-        let original_alpha = alpha;
-        let current_hash = self.transposition_table.hash(game);
-        let mut principal_variation_move = None;
+        score
+    }
 
-        if let Some(transposition_table_entry) = self.transposition_table.fetch(current_hash) {
-            if transposition_table_entry.depth >= depth {
-                match transposition_table_entry.flag {
-                    TranspositionTableFlag::Exact => {
-                        // Check if this move is a repetition
-                        // This might unintentionally cause a draw
-                        game.make_move_sequence(&transposition_table_entry.best_move_sequence);
-                        let count_repetitions = game
-                            .move_history_hash
-                            .iter()
-                            .rev()
-                            .filter(|&&hash| hash == game.current_hash)
-                            .count();
-
-                        game.unmake_move_sequence();
-                        if count_repetitions >= 2 {
-                            // Keep searching
-                        } else {
-                            self.best_score = transposition_table_entry.score;
-                            self.best_move =
-                                Some(transposition_table_entry.best_move_sequence.clone());
-
-                            return transposition_table_entry.score;
-                        }
-                    }
-                    TranspositionTableFlag::LowerBound => {
-                        alpha = alpha.max(transposition_table_entry.score)
-                    }
-                    TranspositionTableFlag::UpperBound => {
-                        beta = beta.min(transposition_table_entry.score)
-                    }
-                    _ => panic!("should not have unknown flag in transposition table"),
-                }
+    /// Search the best `config.multi_pv` root moves one after another.
+    /// Every pass excludes the moves found by the passes before it,
+    /// the lines of the last iteration are only replaced once all passes are done.
+    fn multi_principal_variation_search(&mut self, game: &mut Game, depth: usize) -> i32 {
+        let count = self
+            .config
+            .multi_pv
+            .max(self.config.strength.multi_pv())
+            .min(game.generate_move_sequences().len());
+
+        // A finished game has no moves to rank.
+        if count == 0 || game.is_draw() {
+            return self.search_root(game, depth, -Score::INFINITY, Score::INFINITY);
+        }
 
-                if alpha >= beta {
-                    self.best_score = transposition_table_entry.score;
-                    self.best_move = Some(transposition_table_entry.best_move_sequence.clone());
+        let previous_best_move = (self.best_move.clone(), self.best_score);
+        let mut principal_variations = vec![];
 
-                    return transposition_table_entry.score;
+        for i in 0..count {
+            let score = match self.principal_variations.get(i) {
+                Some((_, previous_score, _)) => {
+                    self.aspiration_search(game, depth, *previous_score)
                 }
+                None => self.search_root(game, depth, -Score::INFINITY, Score::INFINITY),
+            };
+
+            if self.stopped_searching {
+                break;
             }
 
-            principal_variation_move = Some(transposition_table_entry.best_move_sequence.clone());
+            let Some(m) = self.best_move.clone() else {
+                break;
+            };
+
+            let line = self.principal_variation_line(game, &m);
+            self.excluded_moves.push(m.clone());
+            principal_variations.push((m, score, line));
         }
 
+        self.excluded_moves.clear();
+
+        if self.stopped_searching {
+            // The first pass searches all moves, so its move is as good as a finished iteration.
+            (self.best_move, self.best_score) = match principal_variations.first() {
+                Some((m, score, _)) => (Some(m.clone()), *score),
+                None => previous_best_move,
+            };
+
+            return Score::DRAW;
+        }
+
+        principal_variations.sort_by_key(|(_, score, _)| -score);
+        self.best_move = Some(principal_variations[0].0.clone());
+        self.best_score = principal_variations[0].1;
+        self.principal_variations = principal_variations;
+
+        for (i, (_, score, line)) in self.principal_variations.iter().enumerate() {
+            self.send_search_info(depth, i + 1, *score, line.clone());
+        }
+
+        self.best_score
+    }
+
+    /// The principal variation starting with the root move `m`, as stored in the
+    /// transposition table.
+    fn principal_variation_line(&self, game: &mut Game, m: &MoveSequence) -> String {
+        game.make_move_sequence(m);
+        let line = self.transposition_table.get_principal_variation_line(game);
+        game.unmake_move_sequence();
+
+        std::iter::once(m)
+            .chain(line.iter())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn send_search_info(&self, depth: usize, rank: usize, score: i32, principal_variation: String) {
+        let Some(search_info) = &self.search_info else {
+            return;
+        };
+
+        let time = self.time_manager.elapsed();
+        // The nodes of all threads, not only the main thread that sends the info.
+        let nodes = self.shared_nodes.load(Ordering::Relaxed);
+
+        // Nobody is listening anymore if the receiver was dropped.
+        let _ = search_info.send(SearchInfo {
+            depth,
+            selective_depth: self.selective_depth,
+            rank,
+            score,
+            nodes,
+            nodes_per_second: (nodes as f64 / time.as_secs_f64().max(1e-3)) as usize,
+            time,
+            transposition_table_fill: self.transposition_table.fill_permille(),
+            principal_variation,
+        });
+    }
+
+    /// Whether the time is checked at this node, every `config.time_check_interval` nodes.
+    /// The config is a public field, an interval of 0 checks at every node.
+    fn is_time_check(&self) -> bool {
+        self.searched_nodes
+            .is_multiple_of(self.config.time_check_interval.max(1))
+    }
+
+    /// Whether the search has to stop, because a limit is reached or it was told to.
+    fn is_time_up(&self) -> bool {
+        self.is_node_limit_reached()
+            || self.time_manager.is_time_up()
+            || self.stop_signal.load(Ordering::Relaxed)
+    }
+
+    /// Whether the threads together searched as many nodes as the limit allows.
+    fn is_node_limit_reached(&self) -> bool {
+        self.limits
+            .nodes
+            .is_some_and(|nodes| self.shared_nodes.load(Ordering::Relaxed) >= nodes)
+    }
+
+    /// Search the root with a narrow window around the score of the previous iteration.
+    /// If the score falls outside of the window, the window is widened on that side
+    /// and the root is searched again.
+    fn aspiration_search(&mut self, game: &mut Game, depth: usize, previous_score: i32) -> i32 {
+        // Win scores jump around too much between iterations to guess a window.
+        if depth == 1 || previous_score.abs() >= Score::DB_WIN / 2 {
+            return self.search_root(game, depth, -Score::INFINITY, Score::INFINITY);
+        }
+
+        let mut window = ASPIRATION_WINDOW;
+        let mut alpha = previous_score - window;
+        let mut beta = previous_score + window;
+
+        loop {
+            let score = self.search_root(game, depth, alpha, beta);
+
+            if self.stopped_searching {
+                return score;
+            }
+
+            if score <= alpha {
+                alpha = (alpha - window).max(-Score::INFINITY);
+            } else if score >= beta {
+                beta = (beta + window).min(Score::INFINITY);
+            } else {
+                return score;
+            }
+
+            window *= 4;
+        }
+    }
+}
+
+impl Engine {
+    fn search_root(&mut self, game: &mut Game, depth: usize, mut alpha: i32, beta: i32) -> i32 {
+        if let Some(score) = self.game_over_score(game) {
+            return score;
+        }
+
+        trace!(self, node("root", 0, depth, alpha, beta, game));
+
+        let original_alpha = alpha;
+        let current_hash = self.transposition_table.hash(game);
+        let path_dependent_scores = self.path_dependent_scores;
+
+        // The root is always searched, the table only tells which move to try first.
+        // A stored score might have been found with other repetitions on the way to the position.
+        let principal_variation_move = self
+            .transposition_table
+            .fetch(current_hash)
+            .map(|transposition_table_entry| transposition_table_entry.best_move);
+
         let mut best_score = -Score::INFINITY;
         let mut best_move = None;
         let mut available_moves = game.generate_move_sequences();
-        Engine::order_moves(&mut available_moves, &principal_variation_move);
+        available_moves.retain(|m| !self.excluded_moves.contains(m));
+        let principal_variation_move = principal_variation_move
+            .and_then(|packed_move| packed_move.find(&available_moves).cloned());
+        self.move_ordering.order(
+            &mut available_moves,
+            &principal_variation_move,
+            self.ply(game),
+        );
+        self.order_root_moves_by_endgame_table(game, &mut available_moves);
 
-        for m in available_moves {
+        for (i, m) in available_moves.into_iter().enumerate() {
             game.make_move_sequence(&m);
-            let score = -self.search(game, depth - 1, -beta, -alpha);
+            let score = self.principal_variation_search(game, depth - 1, alpha, beta, i == 0);
             game.unmake_move_sequence();
 
             if self.stopped_searching {
                 return Score::DRAW;
             }
 
+            trace!(self, move_searched(0, &m, score));
+
             if score > best_score {
                 best_score = score;
                 best_move = Some(m);
 
                 if score > alpha {
                     if score >= beta {
-                        // Might want to store this in the transposition table
-                        // Also might want to store killer moves
-                        /*  */
+                        if let Some(m) = &best_move {
+                            self.move_ordering.update(m, self.ply(game), depth);
+                            trace!(self, cutoff(self.ply(game), m, score));
+                        }
                         best_score = beta;
                         break;
                     }
 
                     alpha = score;
+
+                    // A new best move during the iteration.
+                    if i > 0 && self.excluded_moves.is_empty() && self.search_info.is_some() {
+                        let line = self.principal_variation_line(game, best_move.as_ref().unwrap());
+                        self.send_search_info(depth, 1, score, line);
+                    }
                 }
             }
         }
 
         if !self.stopped_searching {
-            let transposition_table_entry = TranspositionTableEntry::create_with_key(
-                current_hash,
-                best_move.clone().unwrap(),
-                best_score,
-                depth,
-                if best_score <= original_alpha {
-                    TranspositionTableFlag::UpperBound
-                } else if best_score >= beta {
-                    TranspositionTableFlag::LowerBound
-                } else {
-                    TranspositionTableFlag::Exact
-                },
-            );
+            // Without a searched move, all of them excluded, there is nothing to store.
+            if let Some(m) = best_move
+                .as_ref()
+                .filter(|_| self.excluded_moves.is_empty())
+            {
+                let transposition_table_entry = TranspositionTableEntry::create_with_key(
+                    current_hash,
+                    m.clone(),
+                    Score::to_transposition_table(best_score, self.ply(game)),
+                    depth,
+                    self.transposition_table_flag(
+                        best_score,
+                        original_alpha,
+                        beta,
+                        path_dependent_scores,
+                    ),
+                );
 
-            self.transposition_table.insert(transposition_table_entry);
+                self.transposition_table.insert(transposition_table_entry);
+            }
 
-            self.best_move = best_move;
-            self.best_score = best_score;
+            // If all moves failed low, the best move is not known.
+            if best_score > original_alpha {
+                self.best_move = best_move;
+                self.best_score = best_score;
+            }
         }
 
         best_score
@@ -219,70 +1086,55 @@ impl Engine {
 
     fn search(&mut self, game: &mut Game, depth: usize, mut alpha: i32, mut beta: i32) -> i32 {
         self.searched_nodes += 1;
+        self.shared_nodes.fetch_add(1, Ordering::Relaxed);
+        self.selective_depth = self.selective_depth.max(self.ply(game));
 
-        if self.searched_nodes % CHECK_EVERY_N_NODES == 0 {
-            let end_time = std::time::Instant::now();
-            let elapsed_time = end_time - self.start_time;
-
-            if elapsed_time >= self.max_time {
-                self.stopped_searching = true;
-                return Score::DRAW;
-            }
+        if self.is_node_limit_reached() || (self.is_time_check() && self.is_time_up()) {
+            self.stopped_searching = true;
+            return Score::DRAW;
         }
 
         if depth == 0 {
             return self.quiescence_search(game, alpha, beta);
         }
 
-        if game.is_black_win() {
-            return match game.side_to_move {
-                Color::Black => Score::WIN - game.ply as i32,
-                Color::White => -Score::WIN + game.ply as i32,
-            };
-        } else if game.is_white_win() {
-            return match game.side_to_move {
-                Color::Black => -Score::WIN + game.ply as i32,
-                Color::White => Score::WIN - game.ply as i32,
-            };
-        } else if game.is_draw() {
-            return Score::DRAW;
+        trace!(self, node("node", self.ply(game), depth, alpha, beta, game));
+
+        if let Some(score) = self.game_over_score(game) {
+            return score;
+        }
+
+        // Positions with an exact database result do not need to be searched any further.
+        if let Some(score) = self.endgame_table_score(game) {
+            self.db_hits += 1;
+            trace!(self, endgame_table_hit(self.ply(game), score));
+            return score;
         }
 
         let original_alpha = alpha;
         let current_hash = self.transposition_table.hash(game);
+        let path_dependent_scores = self.path_dependent_scores;
         let mut principal_variation_move = None;
 
-        if let Some(transposition_table_entry) = self.transposition_table.fetch(current_hash) {
+        if let Some(mut transposition_table_entry) = self.transposition_table.fetch(current_hash) {
+            transposition_table_entry.score =
+                Score::from_transposition_table(transposition_table_entry.score, self.ply(game));
+            trace!(
+                self,
+                transposition_table_hit(self.ply(game), &transposition_table_entry)
+            );
+
             if transposition_table_entry.depth >= depth {
                 match transposition_table_entry.flag {
-                    TranspositionTableFlag::Exact => {
-                        // We should first check if this position is a repetition
-                        // so we do not run into a draw
-                        // a draw is bad if we are winning and good if we are losing
-                        // Check if this move is a repetition
-                        // This might unintentionally cause a draw
-                        game.make_move_sequence(&transposition_table_entry.best_move_sequence);
-                        let count_repetitions = game
-                            .move_history_hash
-                            .iter()
-                            .rev()
-                            .filter(|&&hash| hash == game.current_hash)
-                            .count();
-
-                        game.unmake_move_sequence();
-                        if count_repetitions >= 2 {
-                            // Keep searching
-                        } else {
-                            return transposition_table_entry.score;
-                        }
-                    }
+                    TranspositionTableFlag::Exact => return transposition_table_entry.score,
                     TranspositionTableFlag::LowerBound => {
                         alpha = alpha.max(transposition_table_entry.score)
                     }
                     TranspositionTableFlag::UpperBound => {
                         beta = beta.min(transposition_table_entry.score)
                     }
-                    _ => panic!("should not have unknown flag in transposition table"),
+                    // Only the move is known.
+                    TranspositionTableFlag::Unknown => (),
                 }
 
                 if alpha >= beta {
@@ -290,32 +1142,89 @@ impl Engine {
                 }
             }
 
-            principal_variation_move = Some(transposition_table_entry.best_move_sequence.clone());
+            principal_variation_move = Some(transposition_table_entry.best_move);
         }
 
         let mut best_score = -Score::INFINITY;
         let mut best_move = None;
         let mut available_moves = game.generate_move_sequences();
-        Engine::order_moves(&mut available_moves, &principal_variation_move);
+        let principal_variation_move = principal_variation_move
+            .and_then(|packed_move| packed_move.find(&available_moves).cloned());
+        self.move_ordering.order(
+            &mut available_moves,
+            &principal_variation_move,
+            self.ply(game),
+        );
 
-        for m in available_moves {
+        // Reductions and pruning are only used in quiet positions
+        // that are not covered by the endgame database.
+        let can_prune = !available_moves[0].is_capture() && (game.white | game.black).count() > 6;
+        let futility_score = if self.config.futility_pruning
+            && can_prune
+            && depth <= FUTILITY_MARGINS.len()
+            && beta - alpha == 1
+            && alpha.abs() < Score::DB_WIN / 2
+        {
+            Some(self.evaluate(game) + FUTILITY_MARGINS[depth - 1])
+        } else {
+            None
+        };
+
+        let is_single_reply = available_moves.len() == 1;
+
+        for (i, m) in available_moves.into_iter().enumerate() {
+            let is_promotion = m.is_promotion();
             game.make_move_sequence(&m);
-            let score = -self.search(game, depth - 1, -beta, -alpha);
+            let gives_capture = Engine::has_captures(game);
+            let is_quiet = can_prune && !is_promotion && !gives_capture;
+            let extension = self.extension(is_single_reply, gives_capture, is_promotion);
+
+            // Futility pruning
+            if let Some(futility_score) = futility_score {
+                if i > 0 && is_quiet && futility_score <= alpha {
+                    game.unmake_move_sequence();
+                    best_score = best_score.max(futility_score);
+                    continue;
+                }
+            }
+
+            self.extensions += extension;
+
+            // Late move reductions
+            let score = if self.config.late_move_reductions
+                && is_quiet
+                && i >= LATE_MOVE_REDUCTION_MOVES
+                && depth >= LATE_MOVE_REDUCTION_DEPTH
+            {
+                // The extension counted above applies to the reduced search and the re-search.
+                let score = -self.search(game, depth - 2 + extension, -alpha - 1, -alpha);
+                if score > alpha && !self.stopped_searching {
+                    self.principal_variation_search(game, depth - 1 + extension, alpha, beta, false)
+                } else {
+                    score
+                }
+            } else {
+                self.principal_variation_search(game, depth - 1 + extension, alpha, beta, i == 0)
+            };
             game.unmake_move_sequence();
+            self.extensions -= extension;
 
             if self.stopped_searching {
                 return Score::DRAW;
             }
 
+            trace!(self, move_searched(self.ply(game), &m, score));
+
             if score > best_score {
                 best_score = score;
                 best_move = Some(m);
 
                 if score > alpha {
                     if score >= beta {
-                        // Might want to store this in the transposition table
-                        // Also might want to store killer moves
-                        /*  */
+                        if let Some(m) = &best_move {
+                            self.move_ordering.update(m, self.ply(game), depth);
+                            trace!(self, cutoff(self.ply(game), m, score));
+                        }
                         best_score = beta;
                         break;
                     }
@@ -325,23 +1234,19 @@ impl Engine {
             }
         }
 
-        // if best_move.is_none() {
-        //     println!("side to move: {:?}, depht: {}, best score: {}, alpha: {}, beta: {}, original alpha: {} ",game.side_to_move,  depth, best_score, alpha, beta, original_alpha);
-        // }
-
-        if !self.stopped_searching {
+        // Without a searched move there is nothing to store.
+        if let Some(best_move) = best_move.filter(|_| !self.stopped_searching) {
             let transposition_table_entry = TranspositionTableEntry::create_with_key(
                 current_hash,
-                best_move.unwrap(),
-                best_score,
+                best_move,
+                Score::to_transposition_table(best_score, self.ply(game)),
                 depth,
-                if best_score <= original_alpha {
-                    TranspositionTableFlag::UpperBound
-                } else if best_score >= beta {
-                    TranspositionTableFlag::LowerBound
-                } else {
-                    TranspositionTableFlag::Exact
-                },
+                self.transposition_table_flag(
+                    best_score,
+                    original_alpha,
+                    beta,
+                    path_dependent_scores,
+                ),
             );
 
             self.transposition_table.insert(transposition_table_entry);
@@ -350,34 +1255,192 @@ impl Engine {
         best_score
     }
 
+    /// Plies to search a move deeper. Forced lines are extended,
+    /// as long as the line has not been extended too often already.
+    fn extension(&self, is_single_reply: bool, gives_capture: bool, is_promotion: bool) -> usize {
+        let is_forcing = (self.config.single_reply_extensions && is_single_reply)
+            || (self.config.capture_extensions && gives_capture)
+            || (self.config.promotion_extensions && is_promotion);
+
+        usize::from(is_forcing && self.extensions < self.config.max_extensions)
+    }
+
+    /// Search a move that has already been made, from the perspective of the side that made it.
+    /// Only the first move is searched with the full window. All other moves are searched
+    /// with a null window to prove that they are worse, and searched again if they are not.
+    fn principal_variation_search(
+        &mut self,
+        game: &mut Game,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        is_first_move: bool,
+    ) -> i32 {
+        if is_first_move {
+            return -self.search(game, depth, -beta, -alpha);
+        }
+
+        let score = -self.search(game, depth, -alpha - 1, -alpha);
+        if score > alpha && score < beta && !self.stopped_searching {
+            return -self.search(game, depth, -beta, -alpha);
+        }
+
+        score
+    }
+
+    /// The flag to store a score with. A score that saw a repetition anywhere below the position
+    /// depends on the path to it, not only on the position itself. It is only stored to remember
+    /// the move, a draw by repetition might not be one on another path and the other way round.
+    /// The same goes for a score that saw a draw valued with contempt, which changes with
+    /// the root of the search and the side to move there.
+    fn transposition_table_flag(
+        &self,
+        best_score: i32,
+        original_alpha: i32,
+        beta: i32,
+        path_dependent_scores: usize,
+    ) -> TranspositionTableFlag {
+        if self.path_dependent_scores != path_dependent_scores {
+            TranspositionTableFlag::Unknown
+        } else if best_score <= original_alpha {
+            TranspositionTableFlag::UpperBound
+        } else if best_score >= beta {
+            TranspositionTableFlag::LowerBound
+        } else {
+            TranspositionTableFlag::Exact
+        }
+    }
+
+    /// Whether the position repeats an earlier one. Inside the search a single repetition
+    /// counts as a draw, because the side that repeated could repeat it again. Positions from
+    /// before the search have to occur twice, like a threefold repetition in the game.
+    fn is_repetition(&self, game: &Game) -> bool {
+        let reversible_moves = game
+            .move_history
+            .iter()
+            .rev()
+            .take_while(|m| !m.is_irreversible())
+            .count();
+
+        let mut occurrences = 0;
+
+        // The same side has to be on move, so only every second position can be a repetition.
+        for plies_back in (2..=reversible_moves).step_by(2) {
+            let hash = game.move_history_hash[game.move_history_hash.len() - plies_back];
+            if hash != game.current_hash {
+                continue;
+            }
+
+            if game.ply - plies_back >= self.root_ply {
+                return true;
+            }
+
+            occurrences += 1;
+            if occurrences >= 2 {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Plies from the root of the search.
+    fn ply(&self, game: &Game) -> usize {
+        game.ply - self.root_ply
+    }
+
+    /// Score of a draw for the side to move at the root, see `DrawPolicy`.
+    fn root_draw_score(&self, game: &Game) -> i32 {
+        let contempt = self.config.contempt;
+        let weights = &self.config.evaluation;
+
+        match self.config.draw_policy {
+            DrawPolicy::Neutral => Score::DRAW,
+            DrawPolicy::Avoid => Score::DRAW - contempt,
+            DrawPolicy::Seek => Score::DRAW + contempt,
+            DrawPolicy::Material => {
+                // The material part of the evaluation, in men.
+                let material = weights.man
+                    * (game.white.count() as i32 - game.black.count() as i32)
+                    + weights.king
+                        * (game.white_kings.count() as i32 - game.black_kings.count() as i32);
+                let material = match game.side_to_move {
+                    Color::Black => -material,
+                    Color::White => material,
+                }
+                .clamp(-3 * weights.man, 3 * weights.man);
+
+                Score::DRAW - contempt * material / weights.man
+            }
+        }
+    }
+
+    /// Score of a draw from the perspective of the side to move.
+    fn draw_score(&mut self, game: &Game) -> i32 {
+        if self.root_draw_score != Score::DRAW {
+            self.path_dependent_scores += 1;
+        }
+
+        match self.ply(game) % 2 {
+            0 => self.root_draw_score,
+            _ => -self.root_draw_score,
+        }
+    }
+
+    /// Score of a finished game from the perspective of the side to move.
+    /// Wins count the plies from the root, repetitions are draws.
+    fn game_over_score(&mut self, game: &mut Game) -> Option<i32> {
+        let win = Score::WIN - self.ply(game) as i32;
+
+        if game.is_black_win() {
+            Some(match game.side_to_move {
+                Color::Black => win,
+                Color::White => -win,
+            })
+        } else if game.is_white_win() {
+            Some(match game.side_to_move {
+                Color::Black => -win,
+                Color::White => win,
+            })
+        } else if self.is_repetition(game) {
+            self.path_dependent_scores += 1;
+            Some(self.draw_score(game))
+        } else if game.is_draw() {
+            Some(self.draw_score(game))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the side to move has a capture available.
+    fn has_captures(game: &Game) -> bool {
+        let (left_forward, right_forward, left_backward, right_backward) = match game.side_to_move {
+            Color::Black => game.generate_black_jumps(&Bitboard::ALL),
+            Color::White => game.generate_white_jumps(&Bitboard::ALL),
+        };
+
+        !(left_forward | right_forward | left_backward | right_backward).is_empty()
+    }
+
     fn quiescence_search(&mut self, game: &mut Game, mut alpha: i32, beta: i32) -> i32 {
         self.searched_nodes += 1;
+        self.shared_nodes.fetch_add(1, Ordering::Relaxed);
+        self.selective_depth = self.selective_depth.max(self.ply(game));
 
-        if self.searched_nodes % CHECK_EVERY_N_NODES == 0 {
-            let end_time = std::time::Instant::now();
-            let elapsed_time = end_time - self.start_time;
-
-            if elapsed_time >= self.max_time {
-                self.stopped_searching = true;
-                return Score::DRAW;
-            }
+        if self.is_node_limit_reached() || (self.is_time_check() && self.is_time_up()) {
+            self.stopped_searching = true;
+            return Score::DRAW;
         }
 
-        if game.is_black_win() {
-            return match game.side_to_move {
-                Color::Black => Score::WIN - game.ply as i32,
-                Color::White => -Score::WIN + game.ply as i32,
-            };
-        } else if game.is_white_win() {
-            return match game.side_to_move {
-                Color::Black => -Score::WIN + game.ply as i32,
-                Color::White => Score::WIN - game.ply as i32,
-            };
-        } else if game.is_draw() {
-            return Score::DRAW;
+        if let Some(score) = self.game_over_score(game) {
+            return score;
         }
 
         let standing_pat = self.evaluate(game);
+        trace!(
+            self,
+            quiescence(self.ply(game), alpha, beta, standing_pat, game)
+        );
 
         if standing_pat >= beta {
             return beta;
@@ -388,7 +1451,8 @@ impl Engine {
         }
 
         let mut available_moves = game.generate_capture_move_sequences();
-        Engine::order_moves(&mut available_moves, &None);
+        self.move_ordering
+            .order(&mut available_moves, &None, self.ply(game));
 
         for m in available_moves {
             game.make_move_sequence(&m);
@@ -412,6 +1476,35 @@ impl Engine {
     }
 }
 
+#[cfg(feature = "trace")]
+impl Engine {
+    /// Write the search tree of the following searches to `path`, replacing an earlier trace.
+    /// Only the events up to `max_ply` plies from the root and of the first `max_nodes` nodes
+    /// of every search are written.
+    pub fn trace_to_file(
+        &mut self,
+        path: &str,
+        max_ply: Option<usize>,
+        max_nodes: Option<usize>,
+    ) -> Result<(), String> {
+        let tracer = SearchTracer::create(path, max_ply, max_nodes)?;
+        self.tracer = Some(Arc::new(std::sync::Mutex::new(tracer)));
+        Ok(())
+    }
+
+    pub fn stop_tracing(&mut self) {
+        if let Some(tracer) = self.tracer.take() {
+            tracer.lock().unwrap().finish();
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
+
 /// Evaluation.
 impl Engine {
     /// Evaluate the current position from the perspective of white.
@@ -423,34 +1516,80 @@ impl Engine {
     /// - Kings: 141 for every king
     /// - positional advantages
     pub fn evaluate(&mut self, game: &Game) -> i32 {
+        // For the endgame table look here http://webdocs.cs.ualberta.ca/~chinook/databases/
+        let flag = self.endgame_lookup_table.probe(game);
+
+        // Exact results are scored like in the search, so a win found here is not worth more than a sooner one.
+        if let Some(score) = flag
+            .as_ref()
+            .and_then(|flag| self.exact_endgame_table_score(game, flag))
+        {
+            self.db_hits += 1;
+            return score;
+        }
+
         // Score increases as white is winning, and decreases as black is winning.
+        let mut score = Engine::evaluate_pieces_with_weights(game, &self.config.evaluation);
+
+        match flag {
+            Some(EndgameTableFlag::MostlyBlackWin) => score += -Score::DB_MOSTLY_WIN_BONUS,
+            Some(EndgameTableFlag::MostlyWhiteWin) => score += Score::DB_MOSTLY_WIN_BONUS,
+            Some(EndgameTableFlag::MostlyDraw) => score = (score as f32 * 0.25) as i32,
+            _ => {}
+        }
+
+        if score.abs() < Score::MIN_WIN {
+            score += self
+                .config
+                .strength
+                .noise(game.current_hash, self.strength_seed);
+        }
+
+        match game.side_to_move {
+            Color::White => {
+                return score;
+            }
+            Color::Black => {
+                return -score;
+            }
+        }
+    }
+
+    /// Material and placement of the pieces from the perspective of white,
+    /// the part of `evaluate` that does not need the endgame database.
+    pub fn evaluate_pieces(game: &Game) -> i32 {
+        Engine::evaluate_pieces_with_weights(game, &EvaluationWeights::DEFAULT)
+    }
+
+    /// `evaluate_pieces` with other weights than the default ones.
+    pub fn evaluate_pieces_with_weights(game: &Game, weights: &EvaluationWeights) -> i32 {
         let mut score = Score::DRAW; /* score = 0 */
 
         // Material
-        score += 1000 * (game.white.count() as i32 - game.black.count() as i32);
+        score += weights.man * (game.white.count() as i32 - game.black.count() as i32);
 
         // Kings
-        score += 410 * (game.white_kings.count() as i32 - game.black_kings.count() as i32);
+        score += weights.king * (game.white_kings.count() as i32 - game.black_kings.count() as i32);
 
         // Men advantages
-        score += 10
+        score += weights.men_light
             * ((game.white & Engine::WHITE_MEN_LIGHT).count() as i32
                 - (game.black & Engine::BLACK_MEN_LIGHT).count() as i32);
-        score += 20
+        score += weights.men_mid
             * ((game.white & Engine::WHITE_MEN_MID).count() as i32
                 - (game.black & Engine::BLACK_MEN_MID).count() as i32);
-        score += 30
+        score += weights.men_strong
             * ((game.white & Engine::WHITE_MEN_STRONG).count() as i32
                 - (game.black & Engine::BLACK_MEN_STRONG).count() as i32);
 
         // Kings advantages
-        score += 20
+        score += weights.kings_light
             * ((game.white_kings & Engine::WHITE_KINGS_LIGHT).count() as i32
                 - (game.black_kings & Engine::BLACK_KINGS_LIGHT).count() as i32);
-        score += 30
+        score += weights.kings_mid
             * ((game.white_kings & Engine::WHITE_KINGS_MID).count() as i32
                 - (game.black_kings & Engine::BLACK_KINGS_MID).count() as i32);
-        score += 40
+        score += weights.kings_strong
             * ((game.white_kings & Engine::WHITE_KINGS_STRONG).count() as i32
                 - (game.black_kings & Engine::BLACK_KINGS_STRONG).count() as i32);
 
@@ -467,44 +1606,71 @@ impl Engine {
         //     }
         // }
 
-        // For the endgame table look here http://webdocs.cs.ualberta.ca/~chinook/databases/
-        if (game.white | game.black).count() <= 6 {
-            // Use endgame database,
-            // but only if there are not captures one either side possible
-            let w = game.generate_white_jumps(&Bitboard::ALL);
-            let b = game.generate_black_jumps(&Bitboard::ALL);
-            if (w.0 | w.1 | w.2 | w.3 | b.0 | b.1 | b.2 | b.3).count() == 0 {
-                // Assures there are no captures available
-                // Now we can translate the current position / state into a string
-
-                let position = EndgameTable::state_to_string(
-                    game.black,
-                    game.white,
-                    game.black_kings,
-                    game.white_kings,
-                );
+        score
+    }
+}
 
-                if let Some(flag) = self.endgame_lookup_table.fetch(position) {
-                    match flag {
-                        EndgameTableFlag::BlackWin => score += -Score::DB_WIN,
-                        EndgameTableFlag::WhiteWin => score += Score::DB_WIN,
-                        EndgameTableFlag::MostlyBlackWin => score += -Score::DB_MOSTLY_WIN_BONUS,
-                        EndgameTableFlag::MostlyWhiteWin => score += Score::DB_MOSTLY_WIN_BONUS,
-                        EndgameTableFlag::Draw => score = (score as f32 * 0.1) as i32,
-                        EndgameTableFlag::MostlyDraw => score = (score as f32 * 0.25) as i32,
-                    };
-                }
-            }
-        }
+/// Endgame database.
+impl Engine {
+    /// Exact database score from the perspective of the side to move.
+    /// Only slices that are entirely won, lost or drawn count as exact,
+    /// the "mostly" flags are left to the evaluation.
+    /// Wins count the plies from the root like `Score::WIN`, so shorter wins are preferred.
+    fn endgame_table_score(&mut self, game: &Game) -> Option<i32> {
+        let flag = self.endgame_lookup_table.probe(game)?;
+        self.exact_endgame_table_score(game, &flag)
+    }
+
+    /// `endgame_table_score` for a flag that was already probed.
+    fn exact_endgame_table_score(&mut self, game: &Game, flag: &EndgameTableFlag) -> Option<i32> {
+        let score = match flag {
+            EndgameTableFlag::BlackWin => Score::DB_WIN - self.ply(game) as i32,
+            EndgameTableFlag::WhiteWin => -Score::DB_WIN + self.ply(game) as i32,
+            EndgameTableFlag::Draw => return Some(self.draw_score(game)),
+            _ => return None,
+        };
 
         match game.side_to_move {
-            Color::White => {
-                return score;
+            Color::Black => Some(score),
+            Color::White => Some(-score),
+        }
+    }
+
+    /// Use the endgame database on the root moves.
+    /// Moves into won positions are tried first and moves into lost positions are dropped,
+    /// unless every move loses.
+    fn order_root_moves_by_endgame_table(
+        &mut self,
+        game: &mut Game,
+        moves: &mut Vec<MoveSequence>,
+    ) {
+        let mut winning_moves = vec![];
+        let mut other_moves = vec![];
+        let mut losing_moves = vec![];
+
+        for m in moves.drain(..) {
+            game.make_move_sequence(&m);
+            // The score is from the perspective of the opponent.
+            let score = self.endgame_table_score(game);
+            game.unmake_move_sequence();
+
+            match score {
+                Some(score) if score <= -Score::MIN_WIN => winning_moves.push(m),
+                Some(score) if score >= Score::MIN_WIN => losing_moves.push(m),
+                _ => other_moves.push(m),
             }
-            Color::Black => {
-                return -score;
+
+            if score.is_some() {
+                self.db_hits += 1;
             }
         }
+
+        moves.extend(winning_moves);
+        moves.extend(other_moves);
+
+        if moves.is_empty() {
+            moves.extend(losing_moves);
+        }
     }
 }
 
@@ -527,77 +1693,19 @@ impl Engine {
 }
 
 impl Engine {
-    /// Order moves to get better alpha beta pruning.
-    /// The principal variation move is moved to the front of the list.
-    /// The rest of the moves are sorted by score, which factors in
-    /// - captures
-    /// - king captures
-    /// - promotions
-    fn order_moves(moves: &mut Vec<MoveSequence>, principal_variation_move: &Option<MoveSequence>) {
-        // moves.sort_unstable_by(|a, b| {
-        //     let a_score = a.score();
-        //     let b_score = b.score();
-        //     b_score.partial_cmp(&a_score).unwrap()
-        // });
-
-        // // Move the principal variation move to the front of the list.
-        // // Might further improve this by instead of swapping, moving the principal variation move to the front of the list and filling the gap.
-        // if let Some(principal_variation_move) = principal_variation_move {
-        //     let index = moves
-        //         .iter()
-        //         .position(|m| m == principal_variation_move)
-        //         .expect("principal variation move should be in list of moves");
-
-        //     if index > 1 {
-        //         let item = moves.remove(index);
-        //         moves.insert(0, item);
-        //     } else {
-        //         moves.swap(0, index);
-        //     }
-        // }
-
-        if let Some(principal_variation_move) = principal_variation_move {
-            moves.sort_unstable_by(|a, b| {
-                if a == principal_variation_move {
-                    return std::cmp::Ordering::Less;
-                } else if b == principal_variation_move {
-                    return std::cmp::Ordering::Greater;
-                }
-
-                let a_score = a.score();
-                let b_score = b.score();
-                b_score.partial_cmp(&a_score).unwrap()
-            });
-        } else {
-            moves.sort_unstable_by(|a, b| {
-                let a_score = a.score();
-                let b_score = b.score();
-                b_score.partial_cmp(&a_score).unwrap()
-            });
-        }
-        // if principal_variation_move.is_some() && moves.iter().any(|ms| ms.is_promotion()) {
-        //     println!(
-        //         "moves: {:?}, pv: {:?} ",
-        //         moves,
-        //         principal_variation_move.clone()
-        //     );
-        // }
-    }
-}
-
-impl Engine {
-    pub fn translate_score(score: i32, side_of_view: Color, current_ply: usize) -> String {
+    /// Wins are shown with their distance in plies from the searched position.
+    pub fn translate_score(score: i32, side_of_view: Color) -> String {
         // Can probably get along wiothout side of view,
         // match blocks look the same
         match side_of_view {
             Color::Black => {
                 if score > 500_000 {
                     // White is winning but we are black
-                    let distance = Score::WIN - score - current_ply as i32;
+                    let distance = Score::WIN - score;
                     return format!("++ in {}", distance);
                 } else if score < -500_000 {
                     // Black is winning and we are black
-                    let distance = Score::WIN + score - current_ply as i32;
+                    let distance = Score::WIN + score;
                     return format!("-- in {}", distance);
                 } else if score > 25_000 {
                     return "+".to_string();
@@ -608,11 +1716,11 @@ impl Engine {
             Color::White => {
                 if score > 500_000 {
                     // White is winning and we are white
-                    let distance = Score::WIN - score - current_ply as i32;
+                    let distance = Score::WIN - score;
                     return format!("++ in {}", distance);
                 } else if score < -500_000 {
                     // Black is winning and we are white
-                    let distance = Score::WIN + score - current_ply as i32;
+                    let distance = Score::WIN + score;
                     return format!("-- in {}", distance);
                 } else if score > 25_000 {
                     return "+".to_string();
@@ -629,3 +1737,754 @@ impl Engine {
         );
     }
 }
+
+#[cfg(test)]
+mod engine_tests {
+    use super::*;
+
+    fn create_engine() -> Engine {
+        Engine::with_endgame_table(Duration::MAX, EndgameTable::new())
+    }
+
+    /// Length of the shortest forced win of the side to move in plies, by plain minimax.
+    fn shortest_win(game: &mut Game, max_plies: usize) -> Option<usize> {
+        (1..=max_plies).find(|&plies| can_win(game, plies))
+    }
+
+    fn can_win(game: &mut Game, plies: usize) -> bool {
+        plies > 0
+            && game.generate_move_sequences().iter().any(|m| {
+                game.make_move_sequence(m);
+                let won = must_lose(game, plies - 1);
+                game.unmake_move_sequence();
+                won
+            })
+    }
+
+    fn must_lose(game: &mut Game, plies: usize) -> bool {
+        let lost = match game.side_to_move {
+            Color::Black => game.is_white_win(),
+            Color::White => game.is_black_win(),
+        };
+
+        lost || (plies > 0
+            && game.generate_move_sequences().iter().all(|m| {
+                game.make_move_sequence(m);
+                let won = can_win(game, plies - 1);
+                game.unmake_move_sequence();
+                won
+            }))
+    }
+
+    #[test]
+    fn helper_threads_share_the_search() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.threads = 3;
+
+        engine.search_to_depth(&mut game, 6);
+
+        let best_move = engine.best_move.clone().unwrap();
+        assert!(game.generate_move_sequences().contains(&best_move));
+        assert_eq!(game.ply, 0);
+        assert!(engine.searched_nodes > 0);
+    }
+
+    #[tokio::test]
+    async fn ponder_hit_continues_the_search() {
+        // The opening runs into forced captures, which are not searched.
+        let mut game = Game::from_fen("B:WK22,K29:BK1,K2,K4").unwrap();
+        let mut engine = create_engine();
+        engine.max_time = Duration::from_millis(50);
+
+        let best_move = engine.find_best_move(&mut game).await.best_move().cloned();
+        game.make_move_sequence(&best_move.unwrap());
+
+        let expected_move = engine.start_pondering(&game).unwrap();
+        assert!(engine.is_pondering());
+        game.make_move_sequence(&expected_move);
+
+        let best_move = engine.find_best_move(&mut game).await.best_move().cloned();
+        assert!(engine.ponder_hit);
+        assert!(!engine.is_pondering());
+        assert!(game.generate_move_sequences().contains(&best_move.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn ponder_miss_starts_a_fresh_search() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.max_time = Duration::from_millis(50);
+
+        let best_move = engine.find_best_move(&mut game).await.best_move().cloned();
+        game.make_move_sequence(&best_move.unwrap());
+
+        let expected_move = engine.start_pondering(&game).unwrap();
+        let other_move = game
+            .generate_move_sequences()
+            .into_iter()
+            .find(|m| *m != expected_move)
+            .unwrap();
+        game.make_move_sequence(&other_move);
+
+        let best_move = engine.find_best_move(&mut game).await.best_move().cloned();
+        assert!(!engine.ponder_hit);
+        assert!(game.generate_move_sequences().contains(&best_move.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn stopped_search_returns_the_best_move_so_far() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+
+        let stop_handle = engine.stop_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            stop_handle.stop();
+        });
+
+        let best_move = engine.find_best_move(&mut game).await.best_move().cloned();
+        assert!(game.generate_move_sequences().contains(&best_move.unwrap()));
+        assert!(engine.search_time < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn stop_before_the_search_runs_is_not_lost() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        let stop_handle = engine.stop_handle();
+
+        let search = engine.find_best_move(&mut game);
+        stop_handle.stop();
+        let engine_move = tokio::time::timeout(Duration::from_secs(10), search)
+            .await
+            .expect("the search missed the stop");
+
+        assert!(game
+            .generate_move_sequences()
+            .contains(engine_move.best_move().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn dropped_search_is_stopped_before_the_next_one() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+
+        // Without a time limit the search only ends when it is stopped.
+        let search = engine.find_best_move(&mut game);
+        assert!(tokio::time::timeout(Duration::from_millis(50), search)
+            .await
+            .is_err());
+
+        // A search still running would add its nodes to the limit of this one.
+        engine.search_with_limits(&mut game, SearchLimits::nodes(10_000));
+        assert_eq!(engine.searched_nodes, 10_000);
+        assert!(engine.dropped_searches.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn one_engine_plays_both_sides_and_many_games() {
+        let mut engine = create_engine();
+        engine.max_time = Duration::from_millis(20);
+
+        for _ in 0..2 {
+            let mut game = Game::new();
+            for _ in 0..6 {
+                let best_move = engine.find_best_move(&mut game).await.best_move().cloned();
+                game.make_move_sequence(&best_move.unwrap());
+            }
+
+            engine.new_game();
+            assert!(engine.best_move.is_none());
+            assert!(engine.principal_variations.is_empty());
+        }
+
+        // The score is from the perspective of white, whichever side is to move.
+        // The side to move captures the last piece of the other side, in one of two ways.
+        for (fen, is_white_win) in [("W:W22,23:B18", true), ("B:W18:B14,15", false)] {
+            let mut game = Game::from_fen(fen).unwrap();
+            let EngineMove::BestMove { score, .. } = engine.find_best_move(&mut game).await else {
+                panic!("{} has more than one move", fen);
+            };
+            assert_eq!(score > Score::MIN_WIN, is_white_win, "{}", fen);
+            assert_eq!(score < -Score::MIN_WIN, !is_white_win, "{}", fen);
+        }
+    }
+
+    #[tokio::test]
+    async fn finished_games_are_not_searched() {
+        let mut engine = create_engine();
+        engine.search_to_depth(&mut Game::new(), 4);
+        assert!(engine.searched_nodes > 0);
+
+        let mut game = Game::from_fen("B:W18:B").unwrap();
+        assert_eq!(
+            engine.find_best_move(&mut game).await,
+            EngineMove::GameOver(GameResult::WhiteWin)
+        );
+        // Black is blocked in.
+        let mut game = Game::from_fen("B:W5,6,10:B1").unwrap();
+        assert_eq!(
+            engine.find_best_move(&mut game).await,
+            EngineMove::GameOver(GameResult::WhiteWin)
+        );
+
+        let mut game = Game::from_fen("W:WK18:BK1").unwrap();
+        for m in [
+            "18-22", "1-5", "22-18", "5-1", "18-22", "1-5", "22-18", "5-1",
+        ] {
+            game.make_move_external(m).unwrap();
+        }
+        assert_eq!(
+            engine.find_best_move(&mut game).await,
+            EngineMove::GameOver(GameResult::Draw)
+        );
+        assert_eq!(engine.searched_nodes, 0);
+        assert_eq!(engine.best_score, Score::DRAW);
+        assert!(engine.best_move.is_none());
+    }
+
+    #[tokio::test]
+    async fn a_single_move_is_returned_without_a_search() {
+        let mut game = Game::from_fen("W:W23:B18").unwrap();
+        let mut engine = create_engine();
+        engine.search_to_depth(&mut Game::new(), 4);
+        assert!(engine.searched_nodes > 0);
+
+        let engine_move = engine.find_best_move(&mut game).await;
+
+        assert_eq!(engine_move.best_move().unwrap().to_string(), "23x14");
+        assert!(matches!(engine_move, EngineMove::ForcedMove(_)));
+        assert_eq!(engine.searched_nodes, 0);
+        assert_eq!(engine.best_score, Score::DRAW);
+    }
+
+    #[test]
+    fn solver_answers_without_a_search_as_well() {
+        let mut engine = create_engine();
+        engine.search_to_depth(&mut Game::new(), 4);
+
+        let game = Game::from_fen("W:W23:B18").unwrap();
+        let result = engine.solve(&game, &SearchLimits::default());
+        assert_eq!(result.best_move.unwrap().to_string(), "23x14");
+        assert_eq!(result.nodes, 0);
+
+        let game = Game::from_fen("B:W18:B").unwrap();
+        let result = engine.solve(&game, &SearchLimits::default());
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -Score::WIN);
+        assert_eq!(engine.searched_nodes, 0);
+    }
+
+    #[tokio::test]
+    async fn no_time_still_plays_a_legal_move() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.max_time = Duration::ZERO;
+
+        let engine_move = engine.find_best_move(&mut game).await;
+
+        assert!(matches!(engine_move, EngineMove::BestMove { .. }));
+        assert!(game
+            .generate_move_sequences()
+            .contains(engine_move.best_move().unwrap()));
+    }
+
+    #[test]
+    fn engine_is_created_from_a_config() {
+        let config = EngineConfig::builder()
+            .max_depth(3)
+            .endgame_table_path("")
+            .build()
+            .unwrap();
+        let mut engine = Engine::with_config(Duration::MAX, config).unwrap();
+
+        engine.search_with_limits(&mut Game::new(), SearchLimits::infinite());
+        assert_eq!(engine.current_depth, 3);
+
+        let config = EngineConfig::builder()
+            .endgame_table_path("./missing/DB6.idx")
+            .build()
+            .unwrap();
+        assert!(Engine::with_config(Duration::MAX, config)
+            .unwrap_err()
+            .starts_with("could not read ./missing/DB6.idx"));
+    }
+
+    #[test]
+    fn engines_share_the_endgame_table() {
+        let engine = create_engine();
+        let other = Engine::with_endgame_table(Duration::MAX, engine.endgame_table());
+
+        assert!(Arc::ptr_eq(&engine.endgame_table(), &other.endgame_table()));
+    }
+
+    #[test]
+    fn time_check_interval_of_zero_checks_every_node() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.time_check_interval = 0;
+
+        engine.search_to_depth(&mut game, 4);
+        assert!(engine.best_move.is_some());
+    }
+
+    #[test]
+    fn search_stops_at_the_node_limit() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+
+        engine.search_with_limits(&mut game, SearchLimits::nodes(10_000));
+
+        assert_eq!(engine.searched_nodes, 10_000);
+        assert!(engine.best_move.is_some());
+    }
+
+    #[test]
+    fn node_limit_counts_the_nodes_of_all_threads() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.threads = 4;
+
+        engine.search_with_limits(&mut game, SearchLimits::nodes(10_000));
+
+        // Each thread counts the node it stops at.
+        assert!(engine.searched_nodes < 10_000 + 4);
+        assert!(engine.best_move.is_some());
+    }
+
+    #[tokio::test]
+    async fn clock_limits_the_time_for_a_move() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        let limits = SearchLimits::clock(Duration::from_secs(1), Duration::ZERO, Some(10));
+
+        let engine_move = engine.find_best_move_with_limits(&mut game, limits).await;
+
+        assert!(engine_move.best_move().is_some());
+        assert!(engine.search_time < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn multi_pv_ranks_distinct_moves() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.multi_pv = 3;
+
+        let score = engine.search_to_depth(&mut game, 6);
+
+        let principal_variations = &engine.principal_variations;
+        assert_eq!(principal_variations.len(), 3);
+        assert_eq!(principal_variations[0].1, score);
+        assert_eq!(engine.best_move.as_ref(), Some(&principal_variations[0].0));
+
+        for (i, (m, score, line)) in principal_variations.iter().enumerate() {
+            assert!(game.generate_move_sequences().contains(m));
+            assert!(line.starts_with(&m.to_string()));
+            assert!(principal_variations[..i]
+                .iter()
+                .all(|(other, other_score, _)| { other != m && other_score >= score }));
+        }
+    }
+
+    #[test]
+    fn weak_levels_search_less() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.strength = Strength::level(3);
+
+        engine.search_to_depth(&mut game, 10);
+        assert!(engine.current_depth <= 3);
+        assert!(engine.searched_nodes <= 500);
+    }
+
+    #[test]
+    fn weak_levels_play_the_second_best_move_within_the_margin() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.strength = Strength {
+            second_best_chance: 100,
+            second_best_margin: Score::INFINITY,
+            ..Strength::default()
+        };
+
+        let score = engine.search_to_depth(&mut game, 6);
+        assert_eq!(engine.principal_variations.len(), 2);
+        assert_eq!(
+            engine.best_move.as_ref(),
+            Some(&engine.principal_variations[1].0)
+        );
+        assert_eq!(score, engine.principal_variations[1].1);
+
+        // Giving away a man is outside of the margin.
+        let mut game = Game::from_fen("B:W17,18,19,21,23,25:B2,5,10,11,12,14").unwrap();
+        engine.config.strength.second_best_margin = 500;
+        engine.search_to_depth(&mut game, 8);
+        assert_eq!(engine.best_move.clone().unwrap().to_string(), "2-7");
+        assert!(engine.principal_variations[0].1 - engine.principal_variations[1].1 > 500);
+    }
+
+    #[test]
+    fn search_info_is_sent_for_every_iteration() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.transposition_table_size = 1;
+        let mut receiver = engine.subscribe_search_info();
+
+        let score = engine.search_to_depth(&mut game, 8);
+
+        let mut infos = vec![];
+        while let Ok(info) = receiver.try_recv() {
+            infos.push(info);
+        }
+
+        let last_info = infos.last().unwrap();
+        assert_eq!(last_info.depth, 8);
+        assert_eq!(last_info.score, score);
+        assert!(last_info.selective_depth >= 8);
+        assert!(last_info.transposition_table_fill > 0);
+        assert!(last_info
+            .principal_variation
+            .starts_with(&engine.best_move.clone().unwrap().to_string()));
+        assert!((1..=8).all(|depth| infos.iter().any(|info| info.depth == depth)));
+    }
+
+    #[test]
+    fn search_info_counts_the_nodes_of_all_threads() {
+        let mut game = Game::new();
+        let mut engine = create_engine();
+        engine.config.threads = 4;
+        let mut receiver = engine.subscribe_search_info();
+
+        engine.search_to_depth(&mut game, 10);
+
+        let mut last_info = None;
+        while let Ok(info) = receiver.try_recv() {
+            last_info = Some(info);
+        }
+        // The helpers are stopped right after the last iteration of the main thread.
+        assert!(last_info.unwrap().nodes * 2 > engine.searched_nodes);
+    }
+
+    #[test]
+    fn finds_the_shortest_win() {
+        for fen in ["B:WK32:BK18,K19,K27", "B:W30,31:BK14,K15,K23"] {
+            let mut game = Game::from_fen(fen).unwrap();
+            let plies = shortest_win(&mut game, 11).unwrap();
+            let mut engine = create_engine();
+
+            let score = engine.search_to_depth(&mut game, plies + 4);
+            assert_eq!(score, Score::WIN - plies as i32, "{}", fen);
+
+            game.make_move_sequence(&engine.best_move.clone().unwrap());
+            assert!(must_lose(&mut game, plies - 1), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn win_distance_is_kept_in_the_transposition_table() {
+        let mut game = Game::from_fen("B:W30,31:BK14,K15,K23").unwrap();
+        let mut engine = create_engine();
+        engine.search_to_depth(&mut game, 13);
+
+        // The positions on the principal variation are stored deeper than they are searched next.
+        let line = engine
+            .transposition_table
+            .get_principal_variation_line(&mut game);
+        for m in line[..2].iter() {
+            game.make_move_sequence(m);
+        }
+
+        let plies = shortest_win(&mut game, 11).unwrap();
+        assert_eq!(
+            engine.search_to_depth(&mut game, 9),
+            Score::WIN - plies as i32
+        );
+    }
+
+    #[test]
+    fn losing_side_repeats_the_position() {
+        let mut game = Game::from_fen("W:WK18:BK1,K3,K4").unwrap();
+        for m in [
+            "18-22", "1-5", "22-17", "5-1", "17-22", "1-5", "22-18", "5-1",
+        ] {
+            game.make_move_external(m).unwrap();
+        }
+
+        let mut engine = create_engine();
+        let score = engine.search_to_depth(&mut game, 8);
+        assert_eq!(score, Score::DRAW);
+
+        game.make_move_sequence(&engine.best_move.clone().unwrap());
+        assert!(game.is_draw());
+    }
+
+    #[test]
+    fn scores_that_saw_a_repetition_are_not_stored_as_exact() {
+        let mut game = Game::from_fen("B:WK31:BK1,K2,K14").unwrap();
+        for m in [
+            "14-10", "31-27", "10-15", "27-31", "15-10", "31-27", "10-14", "27-31",
+        ] {
+            game.make_move_external(m).unwrap();
+        }
+
+        let mut engine = create_engine();
+        let score = engine.search_to_depth(&mut game, 8);
+        assert!(score > Score::DRAW);
+
+        // Black avoids the repetition, but the score still depends on it being one.
+        let hash = engine.transposition_table.hash(&game);
+        let entry = engine.transposition_table.fetch(hash).unwrap();
+        assert_eq!(entry.flag, TranspositionTableFlag::Unknown);
+    }
+
+    #[test]
+    fn draws_valued_with_contempt_are_not_stored_as_exact() {
+        let mut game = Game::from_fen("W:WK18:BK1").unwrap();
+
+        // Every move leads into a drawn database position.
+        let mut endgame_table = EndgameTable::new();
+        for m in game.generate_move_sequences() {
+            game.make_move_sequence(&m);
+            endgame_table.insert(
+                EndgameTable::state_to_string(
+                    game.black,
+                    game.white,
+                    game.black_kings,
+                    game.white_kings,
+                ),
+                EndgameTableFlag::Draw,
+            );
+            game.unmake_move_sequence();
+        }
+        let endgame_table = Arc::new(endgame_table);
+
+        let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table.clone());
+        assert_eq!(engine.search_to_depth(&mut game, 4), Score::DRAW);
+        let hash = engine.transposition_table.hash(&game);
+        let entry = engine.transposition_table.fetch(hash).unwrap();
+        assert_eq!(entry.flag, TranspositionTableFlag::Exact);
+
+        // With contempt, the draw is only worth this much to the side to move at the root.
+        let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table);
+        engine.config.contempt = 100;
+        engine.config.draw_policy = DrawPolicy::Avoid;
+        assert!(engine.search_to_depth(&mut game, 4) < Score::DRAW);
+        let hash = engine.transposition_table.hash(&game);
+        let entry = engine.transposition_table.fetch(hash).unwrap();
+        assert_eq!(entry.flag, TranspositionTableFlag::Unknown);
+    }
+
+    #[test]
+    fn winning_side_avoids_the_repetition() {
+        let mut game = Game::from_fen("B:WK31:BK1,K2,K14").unwrap();
+        for m in [
+            "14-10", "31-27", "10-15", "27-31", "15-10", "31-27", "10-14", "27-31",
+        ] {
+            game.make_move_external(m).unwrap();
+        }
+
+        let mut engine = create_engine();
+        let score = engine.search_to_depth(&mut game, 8);
+        assert!(score > Score::DRAW);
+
+        game.make_move_sequence(&engine.best_move.clone().unwrap());
+        assert!(!game.is_draw());
+    }
+
+    #[test]
+    fn material_contempt_values_the_draw_by_the_material_at_the_root() {
+        let mut game = Game::from_fen("W:WK18:BK1,K3,K4").unwrap();
+        for m in [
+            "18-22", "1-5", "22-17", "5-1", "17-22", "1-5", "22-18", "5-1",
+        ] {
+            game.make_move_external(m).unwrap();
+        }
+
+        let mut engine = create_engine();
+        engine.config.contempt = 100;
+        engine.config.draw_policy = DrawPolicy::Material;
+        // Behind in material, the draw is worth more than an equal position.
+        assert!(engine.search_to_depth(&mut game, 8) > Score::DRAW);
+
+        game.make_move_sequence(&engine.best_move.clone().unwrap());
+        assert!(game.is_draw());
+
+        // For black, who is ahead, the finished game is worth less than an equal position.
+        assert!(engine.search_to_depth(&mut game, 8) < Score::DRAW);
+    }
+
+    #[test]
+    fn engine_avoids_drawing_won_positions() {
+        // Three pieces against two wins, but at depth 8 black only sees a repetition
+        // or lines that give a man back.
+        let fen = "B:WK14,K23:B28,K31,K32";
+
+        let mut engine = create_engine();
+        let score = engine.search_to_depth(&mut Game::from_fen(fen).unwrap(), 8);
+        assert_eq!(score, Score::DRAW);
+        let drawing_move = engine.best_move.clone().unwrap();
+
+        let mut engine = create_engine();
+        engine.config.contempt = 100;
+        engine.config.draw_policy = DrawPolicy::Material;
+        let score = engine.search_to_depth(&mut Game::from_fen(fen).unwrap(), 8);
+        assert_ne!(engine.best_move.clone().unwrap(), drawing_move);
+        // A man ahead, a draw is worth less than playing on.
+        assert!(score > -engine.config.contempt);
+    }
+
+    #[test]
+    fn draw_policy_steers_into_or_away_from_draws() {
+        for (draw_policy, is_draw) in [(DrawPolicy::Seek, true), (DrawPolicy::Avoid, false)] {
+            let mut game = Game::from_fen("W:WK18:BK1").unwrap();
+            for m in [
+                "18-22", "1-5", "22-17", "5-1", "17-22", "1-5", "22-18", "5-1",
+            ] {
+                game.make_move_external(m).unwrap();
+            }
+
+            let mut engine = create_engine();
+            engine.config.contempt = 100;
+            engine.config.draw_policy = draw_policy;
+            engine.search_to_depth(&mut game, 6);
+
+            game.make_move_sequence(&engine.best_move.clone().unwrap());
+            assert_eq!(game.is_draw(), is_draw, "{:?}", draw_policy);
+        }
+    }
+
+    #[test]
+    fn pruning_and_reductions_keep_the_tactical_result() {
+        // Each of these moves wins a man.
+        let positions = [
+            ("W:W10,21,22,26:B4,8,12,14", "26-23"),
+            ("B:W17,18,19,21,23,25:B2,5,10,11,12,14", "2-7"),
+            ("W:W9,K10,26,28,29:B4,15,17,19,21", "10-14"),
+            ("W:W15,21,24,25,26,27,29,30:B1,3,4,7,9,12,16,18", "24-20"),
+        ];
+
+        for (fen, best_move) in positions {
+            let mut game = Game::from_fen(fen).unwrap();
+            let mut engine = create_engine();
+            let score = engine.search_to_depth(&mut game, 8);
+            assert_eq!(engine.best_move.clone().unwrap().to_string(), best_move);
+
+            let mut full_width = create_engine();
+            full_width.config.late_move_reductions = false;
+            full_width.config.futility_pruning = false;
+            assert_eq!(full_width.search_to_depth(&mut game, 8), score);
+            assert!(engine.searched_nodes < full_width.searched_nodes);
+        }
+    }
+
+    #[test]
+    fn extensions_see_forced_lines_deeper() {
+        // 8-11 wins a man, without extensions it is only found at depth 6.
+        let fen = "B:W15,20,21,29,32:B4,8,9,12,14";
+
+        let mut game = Game::from_fen(fen).unwrap();
+        let mut engine = create_engine();
+        engine.config.single_reply_extensions = true;
+        engine.config.capture_extensions = true;
+        engine.config.promotion_extensions = true;
+        engine.config.max_extensions = 2;
+        engine.search_to_depth(&mut game, 3);
+        assert_eq!(engine.best_move.clone().unwrap().to_string(), "8-11");
+
+        // The extensions are off by default.
+        let mut engine = create_engine();
+        engine.search_to_depth(&mut game, 3);
+        assert_ne!(engine.best_move.clone().unwrap().to_string(), "8-11");
+    }
+    fn endgame_table_position(game: &Game) -> String {
+        EndgameTable::state_to_string(game.black, game.white, game.black_kings, game.white_kings)
+    }
+
+    #[test]
+    fn database_wins_are_scored_by_distance_inside_the_search_and_at_the_leaves() {
+        let mut game = Game::from_fen("W:WK29:BK4").unwrap();
+
+        // Every position after a white move and a black reply is won for white.
+        let mut endgame_table = EndgameTable::new();
+        for m in game.generate_move_sequences() {
+            game.make_move_sequence(&m);
+            for reply in game.generate_move_sequences() {
+                game.make_move_sequence(&reply);
+                assert!(!Engine::has_captures(&game));
+                endgame_table.insert(endgame_table_position(&game), EndgameTableFlag::WhiteWin);
+                game.unmake_move_sequence();
+            }
+            game.unmake_move_sequence();
+        }
+        let endgame_table = Arc::new(endgame_table);
+
+        // Probed inside the search.
+        let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table.clone());
+        assert_eq!(engine.search_to_depth(&mut game, 4), Score::DB_WIN - 2);
+        assert!(engine.db_hits > 0);
+
+        // Probed by the evaluation at the horizon, on the same scale.
+        let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table);
+        assert_eq!(engine.search_to_depth(&mut game, 2), Score::DB_WIN - 2);
+        assert!(engine.db_hits > 0);
+    }
+
+    #[test]
+    fn root_moves_into_lost_database_positions_are_dropped() {
+        let mut game = Game::from_fen("W:WK18:BK1").unwrap();
+        let moves = game.generate_move_sequences();
+        let slices: Vec<String> = moves
+            .iter()
+            .map(|m| {
+                game.make_move_sequence(m);
+                let slice = endgame_table_position(&game);
+                game.unmake_move_sequence();
+                slice
+            })
+            .collect();
+
+        // The database is made of slices, several moves can lead into the same one.
+        let losing_slice = slices[0].clone();
+        let winning_slice = slices.iter().find(|&s| *s != losing_slice).unwrap().clone();
+        let mut endgame_table = EndgameTable::new();
+        endgame_table.insert(winning_slice.clone(), EndgameTableFlag::WhiteWin);
+        endgame_table.insert(losing_slice.clone(), EndgameTableFlag::BlackWin);
+
+        let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table);
+        let mut ordered_moves = moves.clone();
+        engine.order_root_moves_by_endgame_table(&mut game, &mut ordered_moves);
+
+        let losing_moves = slices.iter().filter(|&s| *s == losing_slice).count();
+        assert_eq!(ordered_moves.len(), moves.len() - losing_moves);
+        let first_move = moves.iter().position(|m| *m == ordered_moves[0]).unwrap();
+        assert_eq!(slices[first_move], winning_slice);
+        assert_eq!(
+            engine.db_hits,
+            slices
+                .iter()
+                .filter(|&s| *s == winning_slice || *s == losing_slice)
+                .count()
+        );
+    }
+
+    #[test]
+    fn root_moves_are_kept_when_every_move_loses() {
+        let mut game = Game::from_fen("W:WK18:BK1").unwrap();
+        let moves = game.generate_move_sequences();
+
+        let mut endgame_table = EndgameTable::new();
+        for m in &moves {
+            game.make_move_sequence(m);
+            endgame_table.insert(endgame_table_position(&game), EndgameTableFlag::BlackWin);
+            game.unmake_move_sequence();
+        }
+
+        let mut engine = Engine::with_endgame_table(Duration::MAX, endgame_table);
+        let mut ordered_moves = moves.clone();
+        engine.order_root_moves_by_endgame_table(&mut game, &mut ordered_moves);
+
+        assert_eq!(ordered_moves, moves);
+        assert_eq!(engine.db_hits, moves.len());
+    }
+}
@@ -0,0 +1,762 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::*;
+
+/// When the engine steers into or away from draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawPolicy {
+    /// A draw is worth as much as an equal position.
+    Neutral,
+    /// A draw is worth `contempt` less than an equal position, so the engine avoids draws.
+    Avoid,
+    /// A draw is worth `contempt` more than an equal position, so the engine steers into draws.
+    Seek,
+    /// A draw is worth `contempt` less for every man of material the engine is ahead at the root
+    /// and `contempt` more for every man it is behind, counting at most three men.
+    /// Material is weighted like the evaluation, so a king counts as more than a man.
+    /// The engine plays on while it is ahead and settles for a draw while it is behind.
+    Material,
+}
+
+impl DrawPolicy {
+    const NAMES: [&'static str; 4] = ["neutral", "avoid", "seek", "material"];
+}
+
+impl fmt::Display for DrawPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DrawPolicy::Neutral => "neutral",
+            DrawPolicy::Avoid => "avoid",
+            DrawPolicy::Seek => "seek",
+            DrawPolicy::Material => "material",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DrawPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "neutral" => Ok(DrawPolicy::Neutral),
+            "avoid" => Ok(DrawPolicy::Avoid),
+            "seek" => Ok(DrawPolicy::Seek),
+            "material" => Ok(DrawPolicy::Material),
+            _ => Err(format!(
+                "invalid draw policy: {}, expected one of: {}",
+                s,
+                DrawPolicy::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Weights of the evaluation, from the perspective of the side that owns the pieces.
+/// The placement bonuses are given for every piece on the light, mid and strong tiles,
+/// see `Engine::WHITE_MEN_LIGHT` and the like.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvaluationWeights {
+    pub man: i32,
+    /// Added to `man` for a king.
+    pub king: i32,
+    pub men_light: i32,
+    pub men_mid: i32,
+    pub men_strong: i32,
+    pub kings_light: i32,
+    pub kings_mid: i32,
+    pub kings_strong: i32,
+}
+
+impl EvaluationWeights {
+    pub const DEFAULT: EvaluationWeights = EvaluationWeights {
+        man: 1000,
+        king: 410,
+        men_light: 10,
+        men_mid: 20,
+        men_strong: 30,
+        kings_light: 20,
+        kings_mid: 30,
+        kings_strong: 40,
+    };
+}
+
+impl Default for EvaluationWeights {
+    fn default() -> Self {
+        EvaluationWeights::DEFAULT
+    }
+}
+
+/// Options that change how the `Engine` searches.
+/// Use `EngineConfig::builder` to set them by name or from a file, with validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Search quiet moves that are ordered late with a reduced depth first,
+    /// and only search them to the full depth if they turn out to be good.
+    pub late_move_reductions: bool,
+    /// Skip quiet moves near the leaves if the position is too far below alpha
+    /// for a quiet move to catch up.
+    pub futility_pruning: bool,
+    /// Search the move of a position with only one legal move one ply deeper.
+    /// Forced moves do not widen the tree, so they should not use up the depth.
+    pub single_reply_extensions: bool,
+    /// Search moves that leave the opponent a capture one ply deeper,
+    /// so exchanges are seen to the end.
+    pub capture_extensions: bool,
+    /// Search promotions one ply deeper.
+    pub promotion_extensions: bool,
+    /// Most plies the extensions may add to a single line.
+    /// The extensions only apply with a limit above zero.
+    pub max_extensions: usize,
+    /// Deepest iteration of any search, whatever its limits.
+    pub max_depth: usize,
+    /// Number of threads searching in parallel.
+    /// With a single thread no helper threads are started,
+    /// so the search does not depend on thread timing.
+    pub threads: usize,
+    /// Number of best moves the search finds, each with its own principal variation.
+    /// Every extra move needs another search of the root.
+    pub multi_pv: usize,
+    /// Size of the transposition table in megabytes.
+    /// A changed size takes effect at the start of the next search, which clears the table.
+    pub transposition_table_size: usize,
+    /// Number of nodes between two looks at the clock.
+    pub time_check_interval: usize,
+    /// Make searches reproducible: the hash keys are generated from `seed`, a single thread
+    /// searches, and time limits are converted to node limits at a fixed speed.
+    /// The same position and limits then always give the same move, score and node count.
+    pub deterministic: bool,
+    /// Seed for the hash keys of a deterministic search.
+    pub seed: u64,
+    /// Handicaps for weaker play, full strength by default.
+    pub strength: Strength,
+    /// How much the engine prefers to play on over a draw, on the scale of the evaluation.
+    /// Applied from the perspective of the side the engine searches for, see `draw_policy`.
+    /// Without contempt, every policy values a draw like an equal position.
+    pub contempt: i32,
+    pub draw_policy: DrawPolicy,
+    pub evaluation: EvaluationWeights,
+    /// Endgame database loaded by `Engine::with_config`, none if empty.
+    /// Only read by `Engine::with_config`: the other constructors keep the default path
+    /// whichever database they are given, and changing it on a created engine loads nothing.
+    pub endgame_table_path: String,
+}
+
+impl EngineConfig {
+    /// The default options for a deterministic search with the given seed.
+    pub fn deterministic(seed: u64) -> Self {
+        EngineConfig {
+            deterministic: true,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    pub fn builder() -> EngineConfigBuilder {
+        EngineConfigBuilder::default()
+    }
+
+    /// Read the options of a file, see `EngineConfigBuilder::parse`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        EngineConfig::builder().load_file(path)?.build()
+    }
+
+    /// Every option that can be set by name, with its type, range and default.
+    pub fn options() -> Vec<EngineOption> {
+        let default = EngineConfig::default();
+        let evaluation = &default.evaluation;
+
+        vec![
+            EngineOption::bool("late_move_reductions", default.late_move_reductions),
+            EngineOption::bool("futility_pruning", default.futility_pruning),
+            EngineOption::bool("single_reply_extensions", default.single_reply_extensions),
+            EngineOption::bool("capture_extensions", default.capture_extensions),
+            EngineOption::bool("promotion_extensions", default.promotion_extensions),
+            EngineOption::integer("max_extensions", 0, 16, default.max_extensions as i128),
+            EngineOption::integer("max_depth", 1, 128, default.max_depth as i128),
+            EngineOption::integer("threads", 1, 256, default.threads as i128),
+            EngineOption::integer("multi_pv", 1, 64, default.multi_pv as i128),
+            EngineOption::integer(
+                "transposition_table_size",
+                1,
+                65_536,
+                default.transposition_table_size as i128,
+            ),
+            EngineOption::integer(
+                "time_check_interval",
+                1,
+                1_000_000,
+                default.time_check_interval as i128,
+            ),
+            EngineOption::bool("deterministic", default.deterministic),
+            EngineOption::integer("seed", 0, u64::MAX as i128, default.seed as i128),
+            EngineOption::integer(
+                "level",
+                1,
+                Strength::MAX_LEVEL as i128,
+                Strength::MAX_LEVEL as i128,
+            ),
+            EngineOption::integer("contempt", -5_000, 5_000, default.contempt as i128),
+            EngineOption::choice(
+                "draw_policy",
+                &DrawPolicy::NAMES,
+                &default.draw_policy.to_string(),
+            ),
+            // Twelve kings with every placement bonus stay below `Score::MIN_WIN`.
+            EngineOption::integer("eval_man", 1, 5_000, evaluation.man as i128),
+            EngineOption::integer("eval_king", 0, 5_000, evaluation.king as i128),
+            EngineOption::integer("eval_men_light", 0, 1_000, evaluation.men_light as i128),
+            EngineOption::integer("eval_men_mid", 0, 1_000, evaluation.men_mid as i128),
+            EngineOption::integer("eval_men_strong", 0, 1_000, evaluation.men_strong as i128),
+            EngineOption::integer("eval_kings_light", 0, 1_000, evaluation.kings_light as i128),
+            EngineOption::integer("eval_kings_mid", 0, 1_000, evaluation.kings_mid as i128),
+            EngineOption::integer(
+                "eval_kings_strong",
+                0,
+                1_000,
+                evaluation.kings_strong as i128,
+            ),
+            EngineOption::text("endgame_table_path", &default.endgame_table_path),
+        ]
+    }
+
+    /// The value of an option, formatted like it is set.
+    /// `None` for an unknown option, and for `level` if the strength is not one of the levels.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let evaluation = &self.evaluation;
+
+        let value = match name {
+            "late_move_reductions" => self.late_move_reductions.to_string(),
+            "futility_pruning" => self.futility_pruning.to_string(),
+            "single_reply_extensions" => self.single_reply_extensions.to_string(),
+            "capture_extensions" => self.capture_extensions.to_string(),
+            "promotion_extensions" => self.promotion_extensions.to_string(),
+            "max_extensions" => self.max_extensions.to_string(),
+            "max_depth" => self.max_depth.to_string(),
+            "threads" => self.threads.to_string(),
+            "multi_pv" => self.multi_pv.to_string(),
+            "transposition_table_size" => self.transposition_table_size.to_string(),
+            "time_check_interval" => self.time_check_interval.to_string(),
+            "deterministic" => self.deterministic.to_string(),
+            "seed" => self.seed.to_string(),
+            "level" => (1..=Strength::MAX_LEVEL)
+                .find(|&level| Strength::level(level) == self.strength)?
+                .to_string(),
+            "contempt" => self.contempt.to_string(),
+            "draw_policy" => self.draw_policy.to_string(),
+            "eval_man" => evaluation.man.to_string(),
+            "eval_king" => evaluation.king.to_string(),
+            "eval_men_light" => evaluation.men_light.to_string(),
+            "eval_men_mid" => evaluation.men_mid.to_string(),
+            "eval_men_strong" => evaluation.men_strong.to_string(),
+            "eval_kings_light" => evaluation.kings_light.to_string(),
+            "eval_kings_mid" => evaluation.kings_mid.to_string(),
+            "eval_kings_strong" => evaluation.kings_strong.to_string(),
+            "endgame_table_path" => self.endgame_table_path.clone(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            late_move_reductions: true,
+            futility_pruning: true,
+            // Off until a benchmark shows they pay for the larger tree.
+            single_reply_extensions: false,
+            capture_extensions: false,
+            promotion_extensions: false,
+            max_extensions: 0,
+            max_depth: 64,
+            threads: 1,
+            multi_pv: 1,
+            transposition_table_size: 64,
+            time_check_interval: 4_096,
+            deterministic: false,
+            seed: 0,
+            strength: Strength::default(),
+            contempt: 0,
+            draw_policy: DrawPolicy::Neutral,
+            evaluation: EvaluationWeights::default(),
+            endgame_table_path: EndgameTable::DEFAULT_PATH.to_string(),
+        }
+    }
+}
+
+/// How the value of an `EngineOption` is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionType {
+    /// `true` or `false`.
+    Bool,
+    /// A whole number from `min` to `max`.
+    Integer,
+    /// One of the listed values.
+    Choice(Vec<&'static str>),
+    /// Any text.
+    Text,
+}
+
+/// An option of the `EngineConfig`, for front-ends that let the user change them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: &'static str,
+    pub option_type: OptionType,
+    /// Smallest value of an integer option.
+    pub min: Option<i128>,
+    /// Largest value of an integer option.
+    pub max: Option<i128>,
+    pub default: String,
+}
+
+impl EngineOption {
+    fn bool(name: &'static str, default: bool) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Bool,
+            min: None,
+            max: None,
+            default: default.to_string(),
+        }
+    }
+
+    fn integer(name: &'static str, min: i128, max: i128, default: i128) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Integer,
+            min: Some(min),
+            max: Some(max),
+            default: default.to_string(),
+        }
+    }
+
+    fn choice(name: &'static str, values: &[&'static str], default: &str) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Choice(values.to_vec()),
+            min: None,
+            max: None,
+            default: default.to_string(),
+        }
+    }
+
+    fn text(name: &'static str, default: &str) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Text,
+            min: None,
+            max: None,
+            default: default.to_string(),
+        }
+    }
+
+    /// Check that `value` is a valid value of the option.
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match &self.option_type {
+            OptionType::Bool => self.parse_bool(value).map(|_| ()),
+            OptionType::Integer => self.parse_integer(value).map(|_| ()),
+            OptionType::Choice(values) if !values.contains(&value) => Err(format!(
+                "invalid value of {}: {}, expected one of: {}",
+                self.name,
+                value,
+                values.join(", ")
+            )),
+            OptionType::Choice(_) | OptionType::Text => Ok(()),
+        }
+    }
+
+    fn parse_bool(&self, value: &str) -> Result<bool, String> {
+        value.parse().map_err(|_| {
+            format!(
+                "invalid value of {}: {}, expected true or false",
+                self.name, value
+            )
+        })
+    }
+
+    fn parse_integer(&self, value: &str) -> Result<i128, String> {
+        let min = self.min.unwrap_or(i128::MIN);
+        let max = self.max.unwrap_or(i128::MAX);
+
+        match value.parse::<i128>() {
+            Ok(number) if (min..=max).contains(&number) => Ok(number),
+            _ => Err(format!(
+                "invalid value of {}: {}, expected a number from {} to {}",
+                self.name, value, min, max
+            )),
+        }
+    }
+}
+
+/// Builds a validated `EngineConfig`, from the defaults.
+/// Options are set with the typed methods, by name with `set`, or from a file with `load_file`.
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfigBuilder {
+    config: EngineConfig,
+}
+
+impl EngineConfigBuilder {
+    pub fn late_move_reductions(mut self, late_move_reductions: bool) -> Self {
+        self.config.late_move_reductions = late_move_reductions;
+        self
+    }
+
+    pub fn futility_pruning(mut self, futility_pruning: bool) -> Self {
+        self.config.futility_pruning = futility_pruning;
+        self
+    }
+
+    pub fn single_reply_extensions(mut self, single_reply_extensions: bool) -> Self {
+        self.config.single_reply_extensions = single_reply_extensions;
+        self
+    }
+
+    pub fn capture_extensions(mut self, capture_extensions: bool) -> Self {
+        self.config.capture_extensions = capture_extensions;
+        self
+    }
+
+    pub fn promotion_extensions(mut self, promotion_extensions: bool) -> Self {
+        self.config.promotion_extensions = promotion_extensions;
+        self
+    }
+
+    pub fn max_extensions(mut self, max_extensions: usize) -> Self {
+        self.config.max_extensions = max_extensions;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = max_depth;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = threads;
+        self
+    }
+
+    pub fn multi_pv(mut self, multi_pv: usize) -> Self {
+        self.config.multi_pv = multi_pv;
+        self
+    }
+
+    pub fn transposition_table_size(mut self, transposition_table_size: usize) -> Self {
+        self.config.transposition_table_size = transposition_table_size;
+        self
+    }
+
+    pub fn time_check_interval(mut self, time_check_interval: usize) -> Self {
+        self.config.time_check_interval = time_check_interval;
+        self
+    }
+
+    /// Make searches reproducible with the given seed, see `EngineConfig::deterministic`.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.config.deterministic = true;
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn strength(mut self, strength: Strength) -> Self {
+        self.config.strength = strength;
+        self
+    }
+
+    /// Play at one of the levels of `Strength::level`.
+    pub fn level(self, level: usize) -> Self {
+        self.strength(Strength::level(level))
+    }
+
+    pub fn contempt(mut self, contempt: i32) -> Self {
+        self.config.contempt = contempt;
+        self
+    }
+
+    pub fn draw_policy(mut self, draw_policy: DrawPolicy) -> Self {
+        self.config.draw_policy = draw_policy;
+        self
+    }
+
+    pub fn evaluation(mut self, evaluation: EvaluationWeights) -> Self {
+        self.config.evaluation = evaluation;
+        self
+    }
+
+    pub fn endgame_table_path(mut self, endgame_table_path: &str) -> Self {
+        self.config.endgame_table_path = endgame_table_path.to_string();
+        self
+    }
+
+    /// Set an option of `EngineConfig::options` by name, from its value as text.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let option = EngineConfig::options()
+            .into_iter()
+            .find(|option| option.name == name)
+            .ok_or_else(|| format!("unknown option: {}", name))?;
+        option.validate(value)?;
+
+        let config = &mut self.config;
+        let integer = || option.parse_integer(value);
+        let boolean = || option.parse_bool(value);
+
+        match name {
+            "late_move_reductions" => config.late_move_reductions = boolean()?,
+            "futility_pruning" => config.futility_pruning = boolean()?,
+            "single_reply_extensions" => config.single_reply_extensions = boolean()?,
+            "capture_extensions" => config.capture_extensions = boolean()?,
+            "promotion_extensions" => config.promotion_extensions = boolean()?,
+            "max_extensions" => config.max_extensions = integer()? as usize,
+            "max_depth" => config.max_depth = integer()? as usize,
+            "threads" => config.threads = integer()? as usize,
+            "multi_pv" => config.multi_pv = integer()? as usize,
+            "transposition_table_size" => config.transposition_table_size = integer()? as usize,
+            "time_check_interval" => config.time_check_interval = integer()? as usize,
+            "deterministic" => config.deterministic = boolean()?,
+            "seed" => config.seed = integer()? as u64,
+            "level" => config.strength = Strength::level(integer()? as usize),
+            "contempt" => config.contempt = integer()? as i32,
+            "draw_policy" => config.draw_policy = value.parse()?,
+            "eval_man" => config.evaluation.man = integer()? as i32,
+            "eval_king" => config.evaluation.king = integer()? as i32,
+            "eval_men_light" => config.evaluation.men_light = integer()? as i32,
+            "eval_men_mid" => config.evaluation.men_mid = integer()? as i32,
+            "eval_men_strong" => config.evaluation.men_strong = integer()? as i32,
+            "eval_kings_light" => config.evaluation.kings_light = integer()? as i32,
+            "eval_kings_mid" => config.evaluation.kings_mid = integer()? as i32,
+            "eval_kings_strong" => config.evaluation.kings_strong = integer()? as i32,
+            "endgame_table_path" => config.endgame_table_path = value.to_string(),
+            _ => unreachable!("option {} is listed but not set", name),
+        }
+
+        Ok(())
+    }
+
+    /// Set the options of a file, see `parse`.
+    pub fn load_file(self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+
+        self.parse(&contents)
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Set the options of `key = value` lines. Empty lines, lines starting with `#`
+    /// and `[section]` headers are skipped, so a simple TOML file can be read as well.
+    /// Values may be quoted, a `#` after an unquoted value starts a comment.
+    pub fn parse(mut self, contents: &str) -> Result<Self, String> {
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected name = value", number + 1))?;
+            let value = value.trim();
+            let value = match value.strip_prefix('"') {
+                Some(quoted) => quoted
+                    .split_once('"')
+                    .map(|(value, _)| value)
+                    .ok_or_else(|| format!("line {}: missing closing quote", number + 1))?,
+                None => value.split('#').next().unwrap_or_default().trim(),
+            };
+
+            self.set(name.trim(), value)
+                .map_err(|error| format!("line {}: {}", number + 1, error))?;
+        }
+
+        Ok(self)
+    }
+
+    /// The config, if every option is valid.
+    pub fn build(self) -> Result<EngineConfig, String> {
+        for option in EngineConfig::options() {
+            if let Some(value) = self.config.get(option.name) {
+                option.validate(&value)?;
+            }
+        }
+        // Only a strength that is one of the levels has a value for `level`.
+        self.config.strength.validate()?;
+
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod engine_config_tests {
+    use super::*;
+
+    #[test]
+    fn every_option_can_be_set_to_its_default() {
+        let mut builder = EngineConfig::builder().threads(4).contempt(50).level(3);
+        for option in EngineConfig::options() {
+            builder.set(option.name, &option.default).unwrap();
+        }
+        let config = builder.build().unwrap();
+
+        assert_eq!(config, EngineConfig::default());
+        for option in EngineConfig::options() {
+            assert_eq!(config.get(option.name).unwrap(), option.default);
+        }
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let mut builder = EngineConfig::builder();
+
+        assert_eq!(
+            builder.set("hash", "16").unwrap_err(),
+            "unknown option: hash"
+        );
+        assert_eq!(
+            builder.set("threads", "0").unwrap_err(),
+            "invalid value of threads: 0, expected a number from 1 to 256"
+        );
+        assert_eq!(
+            builder.set("futility_pruning", "yes").unwrap_err(),
+            "invalid value of futility_pruning: yes, expected true or false"
+        );
+        assert_eq!(
+            builder.set("draw_policy", "always").unwrap_err(),
+            "invalid value of draw_policy: always, expected one of: neutral, avoid, seek, material"
+        );
+        assert_eq!(builder.build().unwrap(), EngineConfig::default());
+
+        assert_eq!(
+            EngineConfig::builder().multi_pv(0).build().unwrap_err(),
+            "invalid value of multi_pv: 0, expected a number from 1 to 64"
+        );
+    }
+
+    #[test]
+    fn custom_strengths_are_validated() {
+        let strength = Strength {
+            second_best_chance: 50,
+            ..Strength::level(4)
+        };
+        assert_eq!(
+            EngineConfig::builder()
+                .strength(strength)
+                .build()
+                .unwrap()
+                .strength
+                .second_best_chance,
+            50
+        );
+
+        let strength = Strength {
+            second_best_chance: 150,
+            ..Strength::level(4)
+        };
+        assert_eq!(
+            EngineConfig::builder()
+                .strength(strength)
+                .build()
+                .unwrap_err(),
+            "invalid strength: second_best_chance 150, expected a number from 0 to 100"
+        );
+    }
+
+    #[test]
+    fn every_seed_can_be_set() {
+        let config = EngineConfig::builder()
+            .deterministic(u64::MAX)
+            .build()
+            .unwrap();
+        assert_eq!(config.seed, u64::MAX);
+
+        let config = EngineConfig::builder()
+            .parse("seed = 9223372036854775808")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.seed, 1 << 63);
+
+        assert_eq!(
+            EngineConfig::builder().parse("seed = -1").unwrap_err(),
+            "line 1: invalid value of seed: -1, expected a number from 0 to 18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn evaluation_stays_below_a_win() {
+        let mut builder = EngineConfig::builder();
+        for option in EngineConfig::options() {
+            if option.name.starts_with("eval_") {
+                builder
+                    .set(option.name, &option.max.unwrap().to_string())
+                    .unwrap();
+            }
+        }
+        let weights = builder.build().unwrap().evaluation;
+
+        let bonus = weights.men_light
+            + weights.men_mid
+            + weights.men_strong
+            + weights.kings_light
+            + weights.kings_mid
+            + weights.kings_strong;
+        let noise = Strength::MAX_EVALUATION_NOISE;
+        assert!(12 * (weights.man + weights.king + bonus) + noise < Score::MIN_WIN);
+
+        let game = Game::from_fen("W:WK1,K2,K3,K4,K5,K6,K7,K8,K9,K10,K11,K12:B").unwrap();
+        assert!(Engine::evaluate_pieces_with_weights(&game, &weights) < Score::MIN_WIN);
+    }
+
+    #[test]
+    fn options_are_read_from_key_value_lines() {
+        let contents = "
+            # Tournament settings
+            [engine]
+            threads = 2
+            level=7
+            draw_policy = seek   # take the half point
+            endgame_table_path = \"./DB #6/DB6.idx\"
+        ";
+        let config = EngineConfig::builder()
+            .parse(contents)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(config.threads, 2);
+        assert_eq!(config.strength, Strength::level(7));
+        assert_eq!(config.draw_policy, DrawPolicy::Seek);
+        assert_eq!(config.endgame_table_path, "./DB #6/DB6.idx");
+
+        assert_eq!(
+            EngineConfig::builder()
+                .parse("threads = 2\nmulti_pv\n")
+                .unwrap_err(),
+            "line 2: expected name = value"
+        );
+        assert_eq!(
+            EngineConfig::builder()
+                .parse("\ncontempt = lots")
+                .unwrap_err(),
+            "line 2: invalid value of contempt: lots, expected a number from -5000 to 5000"
+        );
+    }
+
+    #[test]
+    fn options_are_loaded_from_a_file() {
+        let path = std::env::temp_dir().join("checkers_engine_config_test.txt");
+        std::fs::write(&path, "max_depth = 12\neval_king = 500\n").unwrap();
+
+        let config = EngineConfig::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.max_depth, 12);
+        assert_eq!(config.evaluation.king, 500);
+        assert!(EngineConfig::from_file(&path)
+            .unwrap_err()
+            .starts_with("could not read"));
+    }
+}
@@ -0,0 +1,108 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::*;
+
+/// Plays the capture that takes the most material, a king counting as two men.
+/// Ties and positions without captures are decided at random.
+#[derive(Debug)]
+pub struct GreedySolver {
+    rng: StdRng,
+}
+
+impl Default for GreedySolver {
+    fn default() -> Self {
+        GreedySolver::new()
+    }
+}
+
+impl GreedySolver {
+    pub fn new() -> Self {
+        GreedySolver {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// A solver that plays the same moves every time.
+    pub fn with_seed(seed: u64) -> Self {
+        GreedySolver {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Material the move captures.
+    fn captured_material(m: &MoveSequence) -> i32 {
+        m.moves()
+            .iter()
+            .filter(|m| m.capture.is_some())
+            .map(|m| match m.is_king_capture {
+                true => 2,
+                false => 1,
+            })
+            .sum()
+    }
+}
+
+impl Solver for GreedySolver {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn solve(&mut self, game: &Game, _limits: &SearchLimits) -> SolverResult {
+        let mut game = game.clone();
+        let moves = game.generate_move_sequences();
+
+        let most_material = moves.iter().map(Self::captured_material).max();
+        let mut best_moves = moves
+            .into_iter()
+            .filter(|m| Some(Self::captured_material(m)) == most_material)
+            .collect::<Vec<MoveSequence>>();
+
+        let best_move = match best_moves.is_empty() {
+            true => None,
+            false => Some(best_moves.swap_remove(self.rng.gen_range(0..best_moves.len()))),
+        };
+
+        // The evaluation after the move, from the perspective of the side that made it.
+        let score = match &best_move {
+            Some(m) => {
+                game.make_move_sequence(m);
+                match game.side_to_move {
+                    Color::White => -Engine::evaluate_pieces(&game),
+                    Color::Black => Engine::evaluate_pieces(&game),
+                }
+            }
+            None => Score::DRAW,
+        };
+
+        SolverResult {
+            principal_variation: best_move.iter().cloned().collect(),
+            best_move,
+            score,
+            nodes: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod greedy_tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_biggest_capture() {
+        // 23x16 takes a man, 23x14x7 takes two of them.
+        let game = Game::from_fen("W:W23:B10,18,19").unwrap();
+        let result = GreedySolver::with_seed(1).solve(&game, &SearchLimits::default());
+
+        assert_eq!(result.best_move.unwrap().to_string(), "23x14x7");
+    }
+
+    #[test]
+    fn returns_no_move_when_the_game_is_over() {
+        let game = Game::from_fen("W:W1:B5,K6").unwrap();
+        let result = GreedySolver::with_seed(1).solve(&game, &SearchLimits::default());
+
+        assert_eq!(result.best_move, None);
+        assert!(result.principal_variation.is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::*;
+
+/// Number of iterations if the limits of a search have neither a node nor a time limit.
+const DEFAULT_ITERATIONS: usize = 10_000;
+/// Evaluation difference that makes a win ten times more likely than a loss.
+/// Used to turn evaluations of unfinished playouts into results, and results into scores.
+const EVALUATION_SCALE: f64 = 1000.0;
+
+/// How the moves of a playout are chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playout {
+    /// Any legal move.
+    Random,
+    /// The move that leaves the best evaluation, ties are broken at random.
+    Evaluation,
+}
+
+/// Options that change how the `MctsSolver` searches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsConfig {
+    /// Weight of the exploration term of UCT. Higher values try rarely visited moves more often.
+    pub exploration: f64,
+    pub playout: Playout,
+    /// Playouts that are not finished after this many plies are scored by the evaluation.
+    pub max_playout_plies: usize,
+    /// Seed for the random choices, so searches can be reproduced. Random if `None`.
+    pub seed: Option<u64>,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            exploration: std::f64::consts::SQRT_2,
+            playout: Playout::Evaluation,
+            max_playout_plies: 60,
+            seed: None,
+        }
+    }
+}
+
+/// A position in the search tree.
+#[derive(Debug)]
+struct Node {
+    /// The move that leads to the position, `None` for the root.
+    move_sequence: Option<MoveSequence>,
+    /// The side that made the move.
+    color: Color,
+    children: Vec<usize>,
+    untried_moves: Vec<MoveSequence>,
+    visits: usize,
+    /// Sum of the results from the perspective of `color`, 1 for a win and 0 for a loss.
+    value: f64,
+}
+
+impl Node {
+    fn new(move_sequence: Option<MoveSequence>, game: &mut Game) -> Self {
+        let mut color = game.side_to_move.clone();
+        color.switch();
+
+        Node {
+            move_sequence,
+            color,
+            children: vec![],
+            untried_moves: match game_result(game) {
+                Some(_) => vec![],
+                None => game.generate_move_sequences(),
+            },
+            visits: 0,
+            value: 0.0,
+        }
+    }
+
+    /// Upper confidence bound of the node's value, as seen from its parent.
+    fn upper_confidence_bound(&self, parent_visits: usize, exploration: f64) -> f64 {
+        self.value / self.visits as f64
+            + exploration * ((parent_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+}
+
+/// Monte Carlo tree search with UCT.
+///
+/// Every iteration selects a path through the tree by the upper confidence bounds of the moves,
+/// adds one new position to it, and finishes the game from there with a playout.
+/// The result of the playout is counted for every position on the path.
+/// The most visited move of the root is played.
+#[derive(Debug)]
+pub struct MctsSolver {
+    pub config: MctsConfig,
+    /// Time for a move in `find_best_move`.
+    pub max_time: Duration,
+    /// Number of iterations of the last search.
+    pub iterations: usize,
+    /// Duration of the last search.
+    pub search_time: Duration,
+    tree: Vec<Node>,
+}
+
+impl MctsSolver {
+    pub fn new(max_time: Duration) -> Self {
+        MctsSolver {
+            config: MctsConfig::default(),
+            max_time,
+            iterations: 0,
+            search_time: Duration::ZERO,
+            tree: vec![],
+        }
+    }
+
+    /// Search for the best move of the side to move for at most `max_time`.
+    /// Returns the best move, the score from the perspective of white and the most visited line.
+    pub fn find_best_move(&mut self, game: &mut Game) -> (Option<MoveSequence>, i32, String) {
+        let (best_move, score) = self.search(game, SearchLimits::move_time(self.max_time));
+
+        (
+            best_move,
+            match game.side_to_move {
+                Color::Black => -score,
+                Color::White => score,
+            },
+            self.principal_variation()
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+        )
+    }
+
+    /// Search the position within the given limits.
+    /// The node limit is the number of iterations, the depth limit is not used.
+    /// Returns the most visited move and its score from the perspective of the side to move.
+    pub fn search(&mut self, game: &mut Game, limits: SearchLimits) -> (Option<MoveSequence>, i32) {
+        let time_manager = TimeManager::new(&limits);
+        let max_iterations = match (limits.nodes, time_manager.maximum_time()) {
+            (Some(nodes), _) => nodes,
+            (None, Some(_)) => usize::MAX,
+            (None, None) => DEFAULT_ITERATIONS,
+        };
+        let mut rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        self.tree = vec![Node::new(None, game)];
+        self.iterations = 0;
+
+        while self.iterations < max_iterations && !time_manager.is_time_up() {
+            self.iterate(game, &mut rng);
+            self.iterations += 1;
+        }
+
+        self.search_time = time_manager.elapsed();
+
+        match self.most_visited_child(0) {
+            Some(child) => {
+                let node = &self.tree[child];
+                let win_rate = node.value / node.visits as f64;
+
+                (node.move_sequence.clone(), Self::score(win_rate))
+            }
+            None => (None, Score::DRAW),
+        }
+    }
+
+    /// The most visited line from the root of the last search.
+    pub fn principal_variation(&self) -> Vec<MoveSequence> {
+        let mut line = vec![];
+        let mut node = 0;
+
+        while let Some(child) = self.most_visited_child(node) {
+            line.push(self.tree[child].move_sequence.clone().unwrap());
+            node = child;
+        }
+
+        line
+    }
+
+    fn iterate(&mut self, game: &mut Game, rng: &mut StdRng) {
+        let mut path = vec![0];
+        let mut node = 0;
+
+        // Selection
+        while self.tree[node].untried_moves.is_empty() && !self.tree[node].children.is_empty() {
+            node = self.select_child(node);
+            game.make_move_sequence(self.tree[node].move_sequence.as_ref().unwrap());
+            path.push(node);
+        }
+
+        // Expansion
+        if !self.tree[node].untried_moves.is_empty() {
+            let untried_moves = &mut self.tree[node].untried_moves;
+            let m = untried_moves.swap_remove(rng.gen_range(0..untried_moves.len()));
+            game.make_move_sequence(&m);
+
+            let child = self.tree.len();
+            self.tree.push(Node::new(Some(m), game));
+            self.tree[node].children.push(child);
+            node = child;
+            path.push(node);
+        }
+
+        // Simulation
+        let white_result = self.playout(game, rng);
+
+        // Backpropagation
+        for &node in path.iter() {
+            let node = &mut self.tree[node];
+            node.visits += 1;
+            node.value += match node.color {
+                Color::White => white_result,
+                Color::Black => 1.0 - white_result,
+            };
+        }
+
+        for _ in 1..path.len() {
+            game.unmake_move_sequence();
+        }
+    }
+
+    fn select_child(&self, node: usize) -> usize {
+        let parent_visits = self.tree[node].visits;
+
+        *self.tree[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| {
+                let a = self.tree[a].upper_confidence_bound(parent_visits, self.config.exploration);
+                let b = self.tree[b].upper_confidence_bound(parent_visits, self.config.exploration);
+                a.total_cmp(&b)
+            })
+            .unwrap()
+    }
+
+    fn most_visited_child(&self, node: usize) -> Option<usize> {
+        self.tree[node]
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|&child| self.tree[child].visits)
+    }
+
+    /// Play the game from the current position and return the result for white,
+    /// 1 for a win, 0 for a loss and 0.5 for a draw.
+    fn playout(&self, game: &mut Game, rng: &mut StdRng) -> f64 {
+        let mut plies = 0;
+
+        let result = loop {
+            if let Some(result) = game_result(game) {
+                break result;
+            }
+
+            if plies == self.config.max_playout_plies {
+                let evaluation = Engine::evaluate_pieces(game) as f64;
+                break 1.0 / (1.0 + 10f64.powf(-evaluation / EVALUATION_SCALE));
+            }
+
+            let moves = game.generate_move_sequences();
+            let m = match self.config.playout {
+                Playout::Random => &moves[rng.gen_range(0..moves.len())],
+                Playout::Evaluation => Self::best_evaluated_move(game, &moves, rng),
+            };
+
+            game.make_move_sequence(m);
+            plies += 1;
+        };
+
+        for _ in 0..plies {
+            game.unmake_move_sequence();
+        }
+
+        result
+    }
+
+    fn best_evaluated_move<'a>(
+        game: &mut Game,
+        moves: &'a [MoveSequence],
+        rng: &mut StdRng,
+    ) -> &'a MoveSequence {
+        let mut best_moves = vec![];
+        let mut best_score = i32::MIN;
+
+        for m in moves {
+            game.make_move_sequence(m);
+            // From the perspective of the side that made the move.
+            let score = match game.side_to_move {
+                Color::White => -Engine::evaluate_pieces(game),
+                Color::Black => Engine::evaluate_pieces(game),
+            };
+            game.unmake_move_sequence();
+
+            if score > best_score {
+                best_score = score;
+                best_moves.clear();
+            }
+            if score == best_score {
+                best_moves.push(m);
+            }
+        }
+
+        best_moves[rng.gen_range(0..best_moves.len())]
+    }
+
+    /// Turn a win rate into a score on the scale of the evaluation.
+    fn score(win_rate: f64) -> i32 {
+        let win_rate = win_rate.clamp(1e-6, 1.0 - 1e-6);
+        (EVALUATION_SCALE * (win_rate / (1.0 - win_rate)).log10()) as i32
+    }
+}
+
+impl Solver for MctsSolver {
+    fn name(&self) -> &str {
+        "mcts"
+    }
+
+    fn solve(&mut self, game: &Game, limits: &SearchLimits) -> SolverResult {
+        let (best_move, score) = self.search(&mut game.clone(), limits.clone());
+
+        SolverResult {
+            best_move,
+            score,
+            principal_variation: self.principal_variation(),
+            nodes: self.iterations,
+        }
+    }
+}
+
+/// Result of a finished game for white, `None` if the game is not over.
+fn game_result(game: &mut Game) -> Option<f64> {
+    if game.is_white_win() {
+        Some(1.0)
+    } else if game.is_black_win() {
+        Some(0.0)
+    } else if game.is_draw() {
+        Some(0.5)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod mcts_tests {
+    use super::*;
+
+    fn create_solver() -> MctsSolver {
+        let mut solver = MctsSolver::new(Duration::MAX);
+        solver.config.seed = Some(1);
+        solver
+    }
+
+    #[test]
+    fn stops_at_the_iteration_limit() {
+        let mut game = Game::new();
+        let mut solver = create_solver();
+
+        let (best_move, _) = solver.search(&mut game, SearchLimits::nodes(500));
+        assert_eq!(solver.iterations, 500);
+        assert!(game.generate_move_sequences().contains(&best_move.unwrap()));
+        // The game is left as it was.
+        assert_eq!(game.to_fen(), Game::new().to_fen());
+        assert!(game.move_history.is_empty());
+    }
+
+    #[test]
+    fn finds_a_move_that_wins_a_man() {
+        let mut game = Game::from_fen("B:W15,20,21,29,32:B4,8,9,12,14").unwrap();
+        let mut solver = create_solver();
+
+        let (best_move, score) = solver.search(&mut game, SearchLimits::nodes(3_000));
+        assert_eq!(best_move.unwrap().to_string(), "8-11");
+        assert!(score > 0);
+    }
+
+    #[test]
+    fn random_playouts_find_a_move_that_wins_a_man() {
+        let mut game = Game::from_fen("B:W17,18,19,21,23,25:B2,5,10,11,12,14").unwrap();
+        let mut solver = create_solver();
+        solver.config.playout = Playout::Random;
+
+        let (best_move, _) = solver.search(&mut game, SearchLimits::nodes(3_000));
+        assert_eq!(best_move.unwrap().to_string(), "2-7");
+    }
+
+    #[test]
+    fn plays_either_side_and_finished_games() {
+        let mut game = Game::new();
+        let mut solver = create_solver();
+        solver.max_time = Duration::from_millis(20);
+
+        for _ in 0..2 {
+            let (best_move, _, _) = solver.find_best_move(&mut game);
+            game.make_move_sequence(&best_move.unwrap());
+        }
+        assert_eq!(game.ply, 2);
+
+        let mut game = Game::from_fen("B:W18:B").unwrap();
+        assert_eq!(solver.find_best_move(&mut game).0, None);
+    }
+
+    #[tokio::test]
+    async fn plays_against_the_engine() {
+        let mut game = Game::new();
+        let mut solver = MctsSolver::new(Duration::from_millis(20));
+        let mut engine = Engine::with_endgame_table(Duration::from_millis(20), EndgameTable::new());
+
+        for _ in 0..5 {
+            let (black_move, _, _) = solver.find_best_move(&mut game);
+            game.make_move_sequence(&black_move.unwrap());
+
+            let white_move = engine.find_best_move(&mut game).await.best_move().cloned();
+            game.make_move_sequence(&white_move.unwrap());
+        }
+
+        assert_eq!(game.ply, 10);
+    }
+}
@@ -1,12 +1,38 @@
-// pub mod dumb;
 pub mod endgame_table;
+pub mod endgame_table_checker;
 pub mod engine;
+pub mod engine_config;
+pub mod greedy;
+pub mod mcts;
+pub mod move_ordering;
 pub mod negamax;
+pub mod proof_number_search;
+pub mod random;
+pub mod registry;
+pub mod search_info;
+pub mod search_limits;
+#[cfg(feature = "trace")]
+pub mod search_tracer;
+pub mod strength;
+pub mod time_manager;
 
-// pub use dumb::DumbSolver;
-// pub use negamax::NegamaxSolver;
 pub use endgame_table::*;
+pub use endgame_table_checker::*;
 pub use engine::*;
+pub use engine_config::*;
+pub use greedy::*;
+pub use mcts::*;
+pub use move_ordering::*;
+pub use negamax::*;
+pub use proof_number_search::*;
+pub use random::*;
+pub use registry::*;
+pub use search_info::*;
+pub use search_limits::*;
+#[cfg(feature = "trace")]
+pub use search_tracer::*;
+pub use strength::*;
+pub use time_manager::*;
 
 use crate::game::*;
 
@@ -19,6 +45,32 @@ impl Score {
     pub const DB_WIN: i32 = 400_000;
     pub const DB_MOSTLY_WIN_BONUS: i32 = 5_000;
     pub const DRAW: i32 = 0;
+    /// Wins, including those of the endgame database, are counted in plies from the root
+    /// of the search, so shorter wins score higher. Scores at least this big are such wins.
+    pub const MIN_WIN: i32 = Score::DB_WIN / 2;
+
+    /// Convert a score counting plies from the root into one counting plies from a position
+    /// `ply` plies from the root, so it is valid wherever the position is reached again.
+    pub fn to_transposition_table(score: i32, ply: usize) -> i32 {
+        if score >= Score::MIN_WIN {
+            score + ply as i32
+        } else if score <= -Score::MIN_WIN {
+            score - ply as i32
+        } else {
+            score
+        }
+    }
+
+    /// Convert a score stored by `to_transposition_table` back for a position `ply` plies from the root.
+    pub fn from_transposition_table(score: i32, ply: usize) -> i32 {
+        if score >= Score::MIN_WIN {
+            score - ply as i32
+        } else if score <= -Score::MIN_WIN {
+            score + ply as i32
+        } else {
+            score
+        }
+    }
 }
 
 // #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -90,13 +142,25 @@ impl Score {
 //     }
 // }
 
-pub trait Solver {
-    /// Returns the best move and score for the current player.
-    fn get_best_move(&mut self) -> (MoveSequence, i32);
+/// The move a `Solver` chose.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolverResult {
+    /// `None` if the side to move has no legal move.
+    pub best_move: Option<MoveSequence>,
+    /// Score from the perspective of the side to move, on the scale of `Score`.
+    pub score: i32,
+    /// The expected line, starting with the best move.
+    pub principal_variation: Vec<MoveSequence>,
+    /// Number of searched positions, 0 for players that do not search.
+    pub nodes: usize,
 }
 
-pub trait Evaluator {
-    fn evaluate(&mut self) -> i32 {
-        0
-    }
+/// A player that chooses a move for the side to move of any position.
+pub trait Solver {
+    /// The name the player is known by, see `PlayerRegistry`.
+    fn name(&self) -> &str;
+
+    /// Choose a move within the limits. Players use the limits that fit them and ignore the rest,
+    /// empty limits leave it to the player how long it thinks.
+    fn solve(&mut self, game: &Game, limits: &SearchLimits) -> SolverResult;
 }
@@ -0,0 +1,185 @@
+use super::*;
+
+/// Number of plies from the root that killer moves are stored for.
+const MAX_KILLER_PLY: usize = 128;
+
+/// Heuristics for ordering quiet moves, learned from beta cutoffs during the search.
+///
+/// - Killer moves: the last two quiet moves that caused a cutoff at the same ply.
+/// - History: how often and how deep a quiet move from one tile to another caused a cutoff,
+///   indexed by side, from and to.
+#[derive(Debug, Clone)]
+pub struct MoveOrdering {
+    killer_moves: Vec<[Option<MoveSequence>; 2]>,
+    history: Box<[[[i32; 64]; 64]; 2]>,
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        MoveOrdering {
+            killer_moves: vec![[None, None]; MAX_KILLER_PLY],
+            history: Box::new([[[0; 64]; 64]; 2]),
+        }
+    }
+
+    /// Prepare for a new search. Killer moves belong to the plies of the last search
+    /// and are cleared, the history is kept but its weight is halved.
+    pub fn age(&mut self) {
+        self.killer_moves.fill([None, None]);
+
+        for side in self.history.iter_mut() {
+            for from in side.iter_mut() {
+                for score in from.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+
+    /// A quiet move caused a beta cutoff `ply` plies from the root.
+    pub fn update(&mut self, move_sequence: &MoveSequence, ply: usize, depth: usize) {
+        if move_sequence.is_capture() {
+            return;
+        }
+
+        if let Some(killers) = self.killer_moves.get_mut(ply) {
+            if killers[0].as_ref() != Some(move_sequence) {
+                killers[1] = killers[0].take();
+                killers[0] = Some(move_sequence.clone());
+            }
+        }
+
+        let score = self.history_score_mut(move_sequence);
+        *score = score.saturating_add((depth * depth) as i32);
+    }
+
+    /// Order moves to get better alpha beta pruning.
+    /// The principal variation move is moved to the front of the list.
+    /// The rest of the moves are sorted by score, which factors in
+    /// - captures
+    /// - king captures
+    /// - promotions
+    ///
+    /// followed by the killer moves and the history of the quiet moves.
+    pub fn order(
+        &self,
+        moves: &mut [MoveSequence],
+        principal_variation_move: &Option<MoveSequence>,
+        ply: usize,
+    ) {
+        let killers = self.killer_moves.get(ply);
+
+        moves.sort_by_cached_key(|m| {
+            let killer_rank = killers
+                .and_then(|killers| killers.iter().position(|killer| killer.as_ref() == Some(m)))
+                .unwrap_or(2);
+
+            (
+                principal_variation_move.as_ref() != Some(m),
+                -m.score(),
+                killer_rank,
+                -self.history_score(m),
+            )
+        });
+    }
+
+    fn history_score(&self, move_sequence: &MoveSequence) -> i32 {
+        self.history[MoveOrdering::side_index(move_sequence)][move_sequence.from()]
+            [move_sequence.to()]
+    }
+
+    fn history_score_mut(&mut self, move_sequence: &MoveSequence) -> &mut i32 {
+        &mut self.history[MoveOrdering::side_index(move_sequence)][move_sequence.from()]
+            [move_sequence.to()]
+    }
+
+    fn side_index(move_sequence: &MoveSequence) -> usize {
+        match move_sequence.side_to_move() {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod move_ordering_tests {
+    use super::*;
+
+    #[test]
+    fn killers_keep_the_last_two_different_cutoffs() {
+        let moves = Game::new().generate_move_sequences();
+        let mut move_ordering = MoveOrdering::new();
+
+        move_ordering.update(&moves[0], 3, 1);
+        move_ordering.update(&moves[0], 3, 1);
+        assert_eq!(
+            move_ordering.killer_moves[3],
+            [Some(moves[0].clone()), None]
+        );
+
+        move_ordering.update(&moves[1], 3, 1);
+        move_ordering.update(&moves[2], 3, 1);
+        assert_eq!(
+            move_ordering.killer_moves[3],
+            [Some(moves[2].clone()), Some(moves[1].clone())]
+        );
+        assert_eq!(move_ordering.killer_moves[2], [None, None]);
+
+        // Captures are ordered first anyway.
+        let capture = Game::from_fen("B:W18,30:B14")
+            .unwrap()
+            .generate_move_sequences()
+            .remove(0);
+        assert!(capture.is_capture());
+        move_ordering.update(&capture, 3, 1);
+        assert_eq!(move_ordering.killer_moves[3][0], Some(moves[2].clone()));
+        assert_eq!(move_ordering.history_score(&capture), 0);
+    }
+
+    #[test]
+    fn history_grows_with_the_depth_and_is_halved_by_age() {
+        let moves = Game::new().generate_move_sequences();
+        let mut move_ordering = MoveOrdering::new();
+
+        move_ordering.update(&moves[0], 0, 3);
+        move_ordering.update(&moves[0], 5, 2);
+        assert_eq!(move_ordering.history_score(&moves[0]), 9 + 4);
+        assert_eq!(move_ordering.history_score(&moves[1]), 0);
+
+        move_ordering.age();
+        assert_eq!(move_ordering.history_score(&moves[0]), 6);
+        assert_eq!(move_ordering.killer_moves[0], [None, None]);
+        assert_eq!(move_ordering.killer_moves[5], [None, None]);
+    }
+
+    #[test]
+    fn principal_variation_move_comes_first_then_captures_killers_and_history() {
+        let quiet_moves = Game::new().generate_move_sequences();
+        // A double jump, `MoveSequence::score` ranks it above a single step.
+        let capture = Game::from_fen("B:W18,27:B14")
+            .unwrap()
+            .generate_move_sequences()
+            .remove(0);
+        let mut move_ordering = MoveOrdering::new();
+        // A killer at another ply does not count.
+        move_ordering.update(&quiet_moves[3], 1, 1);
+        move_ordering.update(&quiet_moves[2], 2, 1);
+        move_ordering.update(&quiet_moves[4], 5, 10);
+
+        let mut moves = quiet_moves.clone();
+        moves.push(capture.clone());
+        move_ordering.order(&mut moves, &Some(quiet_moves[6].clone()), 2);
+
+        assert_eq!(moves[0], quiet_moves[6]);
+        assert_eq!(moves[1], capture);
+        assert_eq!(moves[2], quiet_moves[2]);
+        assert_eq!(moves[3], quiet_moves[4]);
+        assert_eq!(moves[4], quiet_moves[3]);
+    }
+}
@@ -1,61 +1,149 @@
 use super::*;
 
+/// Depth of a search whose limits have none.
+const DEFAULT_DEPTH: usize = 6;
+
+/// Plain negamax without pruning or move ordering, on the evaluation of the pieces.
+/// Searches with iterative deepening, so it can stop at a time or node limit.
+/// A baseline to compare the other players against.
 #[derive(Debug)]
 pub struct NegamaxSolver {
-    game: Game,
-    color: Color,
-    depth: usize,
-    best_move: Option<MoveSequence>,
+    /// Depth of a search whose limits have none.
+    pub depth: usize,
+    nodes: usize,
+    max_nodes: usize,
+    stopped_searching: bool,
+}
+
+impl Default for NegamaxSolver {
+    fn default() -> Self {
+        NegamaxSolver::new(DEFAULT_DEPTH)
+    }
 }
 
 impl NegamaxSolver {
-    pub fn new(game: Game, color: Color, depth: usize) -> Self {
+    pub fn new(depth: usize) -> Self {
         NegamaxSolver {
-            game,
-            color,
             depth,
-            best_move: None,
+            nodes: 0,
+            max_nodes: usize::MAX,
+            stopped_searching: false,
         }
     }
-}
 
-impl NegamaxSolver {
-    pub async fn get_best_move(&mut self) -> (Option<MoveSequence>, i32) {
-        let best_score = self.negamax(self.depth);
-        (self.best_move.clone(), best_score)
-    }
-}
+    /// Score of the position from the perspective of the side to move,
+    /// `None` if the search was stopped.
+    fn negamax(
+        &mut self,
+        game: &mut Game,
+        depth: usize,
+        ply: usize,
+        time_manager: &TimeManager,
+        principal_variation: &mut Vec<MoveSequence>,
+    ) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes > self.max_nodes || time_manager.is_time_up() {
+            self.stopped_searching = true;
+        }
+        if self.stopped_searching {
+            return None;
+        }
 
-impl NegamaxSolver {
-    fn negamax(&mut self, depth: usize) -> i32 {
+        let moves = game.generate_move_sequences();
+        if moves.is_empty() {
+            return Some(-Score::WIN + ply as i32);
+        }
+        if game.is_draw() {
+            return Some(Score::DRAW);
+        }
         if depth == 0 {
-            return self.evaluate();
+            return Some(match game.side_to_move {
+                Color::White => Engine::evaluate_pieces(game),
+                Color::Black => -Engine::evaluate_pieces(game),
+            });
         }
 
-        let mut best_score = i32::MIN;
-        let available_moves = self.game.generate_move_sequences();
+        let mut best_score = -Score::INFINITY;
+        for m in moves {
+            let mut line = vec![];
+
+            game.make_move_sequence(&m);
+            let score = self.negamax(game, depth - 1, ply + 1, time_manager, &mut line);
+            game.unmake_move_sequence();
 
-        for m in available_moves {
-            self.game.make_move_sequence(&m);
-            let score = -self.negamax(depth - 1);
+            let score = -score?;
             if score > best_score {
                 best_score = score;
-                if depth == self.depth {
-                    self.best_move = Some(m);
-                }
+                principal_variation.clear();
+                principal_variation.push(m);
+                principal_variation.extend(line);
             }
-            self.game.unmake_move_sequence();
         }
 
-        best_score
+        Some(best_score)
     }
 }
 
-impl Evaluator for NegamaxSolver {
-    fn evaluate(&mut self) -> i32 {
-        match self.color {
-            Color::White => self.game.white.count() as i32 - self.game.black.count() as i32,
-            Color::Black => self.game.black.count() as i32 - self.game.white.count() as i32,
+impl Solver for NegamaxSolver {
+    fn name(&self) -> &str {
+        "negamax"
+    }
+
+    fn solve(&mut self, game: &Game, limits: &SearchLimits) -> SolverResult {
+        let mut game = game.clone();
+        let time_manager = TimeManager::new(limits);
+        let max_depth = limits.depth.unwrap_or(self.depth).max(1);
+        self.nodes = 0;
+        self.max_nodes = limits.nodes.unwrap_or(usize::MAX);
+        self.stopped_searching = false;
+
+        let mut result = SolverResult {
+            best_move: game.generate_move_sequences().into_iter().next(),
+            score: Score::DRAW,
+            principal_variation: vec![],
+            nodes: 0,
+        };
+
+        for depth in 1..=max_depth {
+            let mut principal_variation = vec![];
+            let Some(score) =
+                self.negamax(&mut game, depth, 0, &time_manager, &mut principal_variation)
+            else {
+                break;
+            };
+
+            result.best_move = principal_variation.first().cloned();
+            result.score = score;
+            result.principal_variation = principal_variation;
         }
+
+        result.nodes = self.nodes;
+        result
+    }
+}
+
+#[cfg(test)]
+mod negamax_tests {
+    use super::*;
+
+    #[test]
+    fn finds_a_move_that_wins_a_man() {
+        let game = Game::from_fen("B:W17,18,19,21,23,25:B2,5,10,11,12,14").unwrap();
+        let result = NegamaxSolver::default().solve(&game, &SearchLimits::depth(7));
+
+        assert_eq!(result.best_move.unwrap().to_string(), "2-7");
+        assert_eq!(result.principal_variation.len(), 7);
+        assert!(result.score > 0);
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let game = Game::new();
+        let result = NegamaxSolver::new(20).solve(&game, &SearchLimits::nodes(1_000));
+
+        assert!(result.nodes <= 1_001);
+        assert!(Game::new()
+            .generate_move_sequences()
+            .contains(&result.best_move.unwrap()));
     }
 }
@@ -0,0 +1,388 @@
+use std::sync::Arc;
+
+use super::*;
+
+/// Proof and disproof numbers of solved positions.
+const INFINITY: u32 = u32::MAX;
+
+/// The game theoretic value of a position, from the perspective of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofResult {
+    Win,
+    Loss,
+    Draw,
+    /// The budget ran out before the position was solved.
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct Proof {
+    pub result: ProofResult,
+    /// The line the proof expects, both sides resisting as long as the proof tree allows.
+    /// Empty if the result is unknown.
+    pub main_line: Vec<MoveSequence>,
+}
+
+/// A position in the proof tree.
+#[derive(Debug)]
+struct Node {
+    /// The move that leads to the position, `None` for the root.
+    move_sequence: Option<MoveSequence>,
+    parent: usize,
+    children: Vec<usize>,
+    /// Number of leaves that have to be proven to prove the position a win for the attacker.
+    proof: u32,
+    /// Number of leaves that have to be disproven to disprove it.
+    disproof: u32,
+}
+
+impl Node {
+    fn new(move_sequence: Option<MoveSequence>, parent: usize, result: Option<bool>) -> Self {
+        let (proof, disproof) = match result {
+            Some(true) => (0, INFINITY),
+            Some(false) => (INFINITY, 0),
+            None => (1, 1),
+        };
+
+        Node {
+            move_sequence,
+            parent,
+            children: vec![],
+            proof,
+            disproof,
+        }
+    }
+
+    fn is_solved(&self) -> bool {
+        self.proof == 0 || self.disproof == 0
+    }
+}
+
+/// Proves whether a position is won, lost or drawn.
+///
+/// Proof-number search grows a tree that proves a single question, whether the attacker wins.
+/// The attacker chooses at OR nodes and the defender at AND nodes. A position is proven once
+/// the attacker has one proven move, or the defender only has proven moves. Draws count as not
+/// proven, so the position is solved twice: once with the side to move as the attacker and,
+/// if that is disproven, once with the opponent as the attacker.
+///
+/// Every iteration expands the most proving node, the leaf that contributes most to both
+/// solving the root. Positions that are exact in the endgame database are leaves.
+#[derive(Debug)]
+pub struct ProofNumberSearch {
+    /// Memory for the proof tree in megabytes. The search gives up once it is full.
+    pub memory: usize,
+    /// Number of expanded positions during the last search.
+    pub nodes: usize,
+    tree: Vec<Node>,
+    endgame_lookup_table: Arc<EndgameTable>,
+}
+
+impl Default for ProofNumberSearch {
+    fn default() -> Self {
+        ProofNumberSearch::new()
+    }
+}
+
+impl ProofNumberSearch {
+    pub fn new() -> Self {
+        ProofNumberSearch::with_endgame_table(EndgameTable::shared())
+    }
+
+    /// Create a search with the given endgame database instead of loading the default one.
+    /// Pass an `Arc<EndgameTable>` to share one database with engines or other searches.
+    pub fn with_endgame_table(endgame_lookup_table: impl Into<Arc<EndgameTable>>) -> Self {
+        ProofNumberSearch {
+            memory: 64,
+            nodes: 0,
+            tree: vec![],
+            endgame_lookup_table: endgame_lookup_table.into(),
+        }
+    }
+
+    /// Solve the position within the given limits. The node limit is the number of expanded
+    /// positions over both proofs, the depth limit is not used.
+    pub fn solve(&mut self, game: &mut Game, limits: SearchLimits) -> Proof {
+        let time_manager = TimeManager::new(&limits);
+        let max_nodes = limits.nodes.unwrap_or(usize::MAX);
+        self.nodes = 0;
+
+        let side_to_move = game.side_to_move.clone();
+        let mut opponent = side_to_move.clone();
+        opponent.switch();
+
+        let (result, main_line) = match self.prove(game, &side_to_move, max_nodes, &time_manager) {
+            Some(true) => (ProofResult::Win, self.main_line(true, true)),
+            Some(false) => match self.prove(game, &opponent, max_nodes, &time_manager) {
+                Some(true) => (ProofResult::Loss, self.main_line(true, false)),
+                Some(false) => (ProofResult::Draw, self.main_line(false, false)),
+                None => (ProofResult::Unknown, vec![]),
+            },
+            None => (ProofResult::Unknown, vec![]),
+        };
+
+        self.tree = vec![];
+
+        Proof { result, main_line }
+    }
+
+    /// Whether `attacker` wins the position, `None` if the budget ran out.
+    fn prove(
+        &mut self,
+        game: &mut Game,
+        attacker: &Color,
+        max_nodes: usize,
+        time_manager: &TimeManager,
+    ) -> Option<bool> {
+        let max_tree_size = self.memory * 1024 * 1024
+            / (std::mem::size_of::<Node>()
+                + std::mem::size_of::<usize>()
+                + std::mem::size_of::<Move>());
+
+        self.tree = vec![Node::new(None, 0, self.result(game, attacker))];
+
+        while !self.tree[0].is_solved() {
+            if self.nodes >= max_nodes
+                || self.tree.len() >= max_tree_size
+                || time_manager.is_time_up()
+            {
+                return None;
+            }
+
+            let node = self.select_most_proving_node(game, attacker);
+            self.expand(game, node, attacker);
+            self.nodes += 1;
+
+            // Back to the root, updating the numbers of the path on the way.
+            let mut node = node;
+            loop {
+                self.update(node, game.side_to_move == *attacker);
+                if node == 0 {
+                    break;
+                }
+                game.unmake_move_sequence();
+                node = self.tree[node].parent;
+            }
+        }
+
+        Some(self.tree[0].proof == 0)
+    }
+
+    /// Follow the moves that decide the proof from the root to a leaf,
+    /// making them on the way.
+    fn select_most_proving_node(&self, game: &mut Game, attacker: &Color) -> usize {
+        let mut node = 0;
+
+        while !self.tree[node].children.is_empty() {
+            let children = self.tree[node].children.iter();
+            node = *if game.side_to_move == *attacker {
+                children.min_by_key(|&&child| self.tree[child].proof)
+            } else {
+                children.min_by_key(|&&child| self.tree[child].disproof)
+            }
+            .unwrap();
+
+            game.make_move_sequence(self.tree[node].move_sequence.as_ref().unwrap());
+        }
+
+        node
+    }
+
+    fn expand(&mut self, game: &mut Game, node: usize, attacker: &Color) {
+        for m in game.generate_move_sequences() {
+            game.make_move_sequence(&m);
+            let result = self.result(game, attacker);
+            game.unmake_move_sequence();
+
+            let child = self.tree.len();
+            self.tree.push(Node::new(Some(m), node, result));
+            self.tree[node].children.push(child);
+        }
+    }
+
+    /// Recompute the numbers of an expanded node from its children.
+    fn update(&mut self, node: usize, is_attacker_to_move: bool) {
+        if self.tree[node].children.is_empty() {
+            return;
+        }
+
+        let children = self.tree[node]
+            .children
+            .iter()
+            .map(|&child| &self.tree[child]);
+        let (proof, disproof) = if is_attacker_to_move {
+            (
+                children.clone().map(|child| child.proof).min().unwrap(),
+                children.fold(0, |sum: u32, child| sum.saturating_add(child.disproof)),
+            )
+        } else {
+            (
+                children
+                    .clone()
+                    .fold(0, |sum: u32, child| sum.saturating_add(child.proof)),
+                children.map(|child| child.disproof).min().unwrap(),
+            )
+        };
+
+        self.tree[node].proof = proof;
+        self.tree[node].disproof = disproof;
+    }
+
+    /// Whether `attacker` has won the position, `None` if the game goes on
+    /// and the endgame database does not know the result.
+    fn result(&self, game: &mut Game, attacker: &Color) -> Option<bool> {
+        let winner = if game.is_white_win() {
+            Color::White
+        } else if game.is_black_win() {
+            Color::Black
+        } else if game.is_draw() {
+            return Some(false);
+        } else {
+            match self.endgame_lookup_table.probe(game)? {
+                EndgameTableFlag::BlackWin => Color::Black,
+                EndgameTableFlag::WhiteWin => Color::White,
+                EndgameTableFlag::Draw => return Some(false),
+                _ => return None,
+            }
+        };
+
+        Some(winner == *attacker)
+    }
+
+    /// The main line of the solved proof tree. The side that wins the proof takes the move with
+    /// the smallest subtree, the fastest way to the result, the other side the move with the
+    /// biggest subtree, the longest resistance.
+    fn main_line(&self, proven: bool, is_attacker_to_move: bool) -> Vec<MoveSequence> {
+        // Children are always added after their parents.
+        let mut sizes = vec![1; self.tree.len()];
+        for node in (1..self.tree.len()).rev() {
+            sizes[self.tree[node].parent] += sizes[node];
+        }
+
+        let mut line = vec![];
+        let mut node = 0;
+        // Whether the side to move at `node` is the one the proof tree is for,
+        // the attacker of a proven tree or the defender of a disproven one.
+        let mut is_winner_to_move = proven == is_attacker_to_move;
+
+        while !self.tree[node].children.is_empty() {
+            let children = self.tree[node].children.iter().copied();
+            node = if is_winner_to_move {
+                children
+                    .filter(|&child| self.is_won(child, proven))
+                    .min_by_key(|&child| sizes[child])
+            } else {
+                children.max_by_key(|&child| sizes[child])
+            }
+            .unwrap();
+
+            line.push(self.tree[node].move_sequence.clone().unwrap());
+            is_winner_to_move = !is_winner_to_move;
+        }
+
+        line
+    }
+
+    /// Whether the node has the result of the proof tree, proven or disproven.
+    fn is_won(&self, node: usize, proven: bool) -> bool {
+        match proven {
+            true => self.tree[node].proof == 0,
+            false => self.tree[node].disproof == 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod proof_number_search_tests {
+    use super::*;
+
+    fn solve(fen: &str, endgame_table: EndgameTable, limits: SearchLimits) -> (Game, Proof) {
+        let mut game = Game::from_fen(fen).unwrap();
+        let mut search = ProofNumberSearch::with_endgame_table(endgame_table);
+        let proof = search.solve(&mut game, limits.clone());
+
+        assert!(search.nodes <= limits.nodes.unwrap_or(usize::MAX));
+        (game, proof)
+    }
+
+    #[test]
+    fn proves_a_forced_win() {
+        let (mut game, proof) = solve(
+            "B:WK32:BK18,K19,K27",
+            EndgameTable::new(),
+            SearchLimits::nodes(100_000),
+        );
+        assert_eq!(proof.result, ProofResult::Win);
+
+        for m in proof.main_line.iter() {
+            game.make_move_sequence(m);
+        }
+        assert!(game.is_black_win());
+    }
+
+    #[test]
+    fn proves_a_forced_loss() {
+        let (mut game, proof) = solve(
+            "B:WK32:BK18,K19,K27",
+            EndgameTable::new(),
+            SearchLimits::nodes(100_000),
+        );
+        game.make_move_sequence(&proof.main_line[0]);
+        let fen = game.to_fen();
+
+        let (mut game, proof) = solve(&fen, EndgameTable::new(), SearchLimits::nodes(100_000));
+        assert_eq!(proof.result, ProofResult::Loss);
+
+        for m in proof.main_line.iter() {
+            game.make_move_sequence(m);
+        }
+        assert!(game.is_black_win());
+    }
+
+    #[test]
+    fn endgame_table_results_are_leaves() {
+        // Black has to capture 10x17, the position after it is in the database.
+        let fen = "B:W14,K29:BK10";
+        let mut game = Game::from_fen(fen).unwrap();
+        let capture = game.generate_move_sequences().remove(0);
+        game.make_move_sequence(&capture);
+        let slice = EndgameTable::state_to_string(
+            game.black,
+            game.white,
+            game.black_kings,
+            game.white_kings,
+        );
+
+        for (flag, result) in [
+            (EndgameTableFlag::BlackWin, ProofResult::Win),
+            (EndgameTableFlag::WhiteWin, ProofResult::Loss),
+            (EndgameTableFlag::Draw, ProofResult::Draw),
+        ] {
+            let mut endgame_table = EndgameTable::new();
+            endgame_table.insert(slice.clone(), flag);
+
+            let (_, proof) = solve(fen, endgame_table, SearchLimits::nodes(10));
+            assert_eq!(proof.result, result);
+            assert_eq!(proof.main_line, vec![capture.clone()]);
+        }
+    }
+
+    #[test]
+    fn gives_up_when_the_budget_runs_out() {
+        let (_, proof) = solve(
+            Game::new().to_fen().as_str(),
+            EndgameTable::new(),
+            SearchLimits::nodes(1_000),
+        );
+        assert_eq!(proof.result, ProofResult::Unknown);
+        assert!(proof.main_line.is_empty());
+
+        let mut game = Game::new();
+        let mut search = ProofNumberSearch::with_endgame_table(EndgameTable::new());
+        search.memory = 0;
+        assert_eq!(
+            search.solve(&mut game, SearchLimits::default()).result,
+            ProofResult::Unknown
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::*;
+
+/// Plays any legal move.
+#[derive(Debug)]
+pub struct RandomSolver {
+    rng: StdRng,
+}
+
+impl Default for RandomSolver {
+    fn default() -> Self {
+        RandomSolver::new()
+    }
+}
+
+impl RandomSolver {
+    pub fn new() -> Self {
+        RandomSolver {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// A solver that plays the same moves every time.
+    pub fn with_seed(seed: u64) -> Self {
+        RandomSolver {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Solver for RandomSolver {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn solve(&mut self, game: &Game, _limits: &SearchLimits) -> SolverResult {
+        let mut moves = game.clone().generate_move_sequences();
+        let best_move = match moves.is_empty() {
+            true => None,
+            false => Some(moves.swap_remove(self.rng.gen_range(0..moves.len()))),
+        };
+
+        SolverResult {
+            principal_variation: best_move.iter().cloned().collect(),
+            best_move,
+            score: Score::DRAW,
+            nodes: 0,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use super::*;
+
+/// Think time of the searching players if the limits of a move have none.
+const DEFAULT_MOVE_TIME: Duration = Duration::from_millis(100);
+
+type PlayerConstructor = Box<dyn Fn() -> Box<dyn Solver>>;
+
+/// Creates players by name, so tools can match any two of them.
+///
+/// The default registry knows `random`, `greedy`, `negamax`, `mcts` and `engine`.
+/// The engine loads the default endgame database.
+pub struct PlayerRegistry {
+    players: Vec<(String, PlayerConstructor)>,
+}
+
+impl Default for PlayerRegistry {
+    fn default() -> Self {
+        let mut registry = PlayerRegistry::new();
+        registry.register("random", || Box::new(RandomSolver::new()));
+        registry.register("greedy", || Box::new(GreedySolver::new()));
+        registry.register("negamax", || Box::new(NegamaxSolver::default()));
+        registry.register("mcts", || Box::new(MctsSolver::new(DEFAULT_MOVE_TIME)));
+        registry.register("engine", || Box::new(Engine::new(DEFAULT_MOVE_TIME)));
+        registry
+    }
+}
+
+impl PlayerRegistry {
+    /// A registry without any players.
+    pub fn new() -> Self {
+        PlayerRegistry { players: vec![] }
+    }
+
+    /// Add a player, replacing the one with the same name.
+    pub fn register(&mut self, name: &str, constructor: impl Fn() -> Box<dyn Solver> + 'static) {
+        self.players.retain(|(player, _)| player != name);
+        self.players.push((name.to_string(), Box::new(constructor)));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.players.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn create(&self, name: &str) -> Result<Box<dyn Solver>, String> {
+        match self.players.iter().find(|(player, _)| player == name) {
+            Some((_, constructor)) => Ok(constructor()),
+            None => Err(format!(
+                "unknown player: {}, expected one of: {}",
+                name,
+                self.names().join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use super::*;
+
+    #[test]
+    fn every_player_plays_a_legal_move() {
+        let mut registry = PlayerRegistry::default();
+        // The default engine needs the endgame database.
+        registry.register("engine", || {
+            Box::new(Engine::with_endgame_table(
+                DEFAULT_MOVE_TIME,
+                EndgameTable::new(),
+            ))
+        });
+
+        let game =
+            Game::from_fen("W:W18,19,21,22,23,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,9,11,12,14,20")
+                .unwrap();
+        let moves = game.clone().generate_move_sequences();
+
+        for name in registry.names() {
+            let mut player = registry.create(name).unwrap();
+            let result = player.solve(&game, &SearchLimits::nodes(2_000));
+
+            assert_eq!(player.name(), name);
+            assert!(moves.contains(&result.best_move.unwrap()), "{}", name);
+            assert!(moves.contains(&result.principal_variation[0]), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unknown_players_are_an_error() {
+        let registry = PlayerRegistry::default();
+
+        assert_eq!(
+            registry.create("stockfish").err().unwrap(),
+            "unknown player: stockfish, expected one of: random, greedy, negamax, mcts, engine"
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Progress of a running search, sent after every finished iteration
+/// and whenever the best move changes during an iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    /// Depth of the iteration.
+    pub depth: usize,
+    /// Deepest ply from the root the iteration reached, including quiescence search.
+    pub selective_depth: usize,
+    /// Rank of the line in a multi-PV search, `1` for the best move.
+    pub rank: usize,
+    /// Score from the perspective of the side to move.
+    pub score: i32,
+    /// Nodes searched so far by all search threads together.
+    pub nodes: usize,
+    pub nodes_per_second: usize,
+    /// Time since the search started.
+    pub time: Duration,
+    /// Permille of the transposition table filled by the current search.
+    pub transposition_table_fill: usize,
+    pub principal_variation: String,
+}
+
+impl fmt::Display for SearchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {} seldepth {} multipv {} score {:+.2} nodes {} nps {} time {} hashfull {} pv {}",
+            self.depth,
+            self.selective_depth,
+            self.rank,
+            self.score as f32 / 1000.0,
+            self.nodes,
+            self.nodes_per_second,
+            self.time.as_millis(),
+            self.transposition_table_fill,
+            self.principal_variation
+        )
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+/// The time left on a player's clock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clock {
+    /// Time left for the rest of the game, or until the next time control.
+    pub remaining: Duration,
+    /// Time added after every move.
+    pub increment: Duration,
+    /// Moves until the next time control. `None` if the remaining time is for the whole game.
+    pub moves_to_go: Option<usize>,
+}
+
+/// Limits for a single search. The search stops at whichever limit is reached first,
+/// without any limits it searches until it is stopped or reaches the maximum depth.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Maximum depth of the iterative deepening.
+    pub depth: Option<usize>,
+    /// Maximum number of nodes, counted over all search threads together.
+    pub nodes: Option<usize>,
+    /// Fixed time for the move.
+    pub move_time: Option<Duration>,
+    /// Search until stopped, ignoring the clock.
+    pub infinite: bool,
+    /// Remaining clock time, the time manager decides how much of it to use.
+    pub clock: Option<Clock>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: usize) -> Self {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(nodes: usize) -> Self {
+        SearchLimits {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> Self {
+        SearchLimits {
+            move_time: Some(move_time),
+            ..Default::default()
+        }
+    }
+
+    pub fn infinite() -> Self {
+        SearchLimits {
+            infinite: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn clock(remaining: Duration, increment: Duration, moves_to_go: Option<usize>) -> Self {
+        SearchLimits {
+            clock: Some(Clock {
+                remaining,
+                increment,
+                moves_to_go,
+            }),
+            ..Default::default()
+        }
+    }
+}
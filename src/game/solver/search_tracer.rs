@@ -0,0 +1,212 @@
+//! Writes the search tree to a file, to find out why the engine played a move.
+//! Only compiled with the `trace` feature, e.g. `cargo run --release --features trace`.
+//!
+//! Every event is one line: the event name, followed by `key=value` fields.
+//! The `ply` field is the distance from the root, so lines can be filtered with `grep`:
+//!
+//! ```text
+//! search fen=B:W18,22:BK1 depth=3 nodes=- time=-
+//! iteration depth=2
+//! root ply=0 depth=2 alpha=-700 beta=-500 fen=B:W18,22:BK1
+//! node ply=1 depth=1 alpha=500 beta=700 fen=W:W18,22:BK6
+//! quiescence ply=2 alpha=-700 beta=-500 standing_pat=-600 fen=B:W14,22:BK6
+//! move ply=1 move=18-14 score=600
+//! tt ply=1 depth=1 flag=Exact score=610
+//! cutoff ply=3 move=15X6 score=999996
+//! ```
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::*;
+use crate::transposition_table::TranspositionTableEntry;
+
+#[derive(Debug)]
+pub struct SearchTracer {
+    writer: BufWriter<File>,
+    /// Events deeper than this ply from the root are left out.
+    max_ply: Option<usize>,
+    /// Only the events of the first nodes of every search are written.
+    max_nodes: Option<usize>,
+    nodes: usize,
+}
+
+impl SearchTracer {
+    pub fn create(
+        path: &str,
+        max_ply: Option<usize>,
+        max_nodes: Option<usize>,
+    ) -> Result<Self, String> {
+        let file = File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+
+        Ok(SearchTracer {
+            writer: BufWriter::new(file),
+            max_ply,
+            max_nodes,
+            nodes: 0,
+        })
+    }
+
+    pub fn start_search(&mut self, game: &Game, limits: &SearchLimits) {
+        self.nodes = 0;
+        self.write(format_args!(
+            "search fen={} depth={} nodes={} time={}",
+            game.to_fen(),
+            Self::limit(limits.depth),
+            Self::limit(limits.nodes),
+            Self::limit(limits.move_time.map(|time| time.as_millis())),
+        ));
+    }
+
+    pub fn iteration(&mut self, depth: usize) {
+        if self.is_within_node_limit() {
+            self.write(format_args!("iteration depth={}", depth));
+        }
+    }
+
+    /// A node of the search, `event` tells the root apart from the other nodes.
+    pub fn node(
+        &mut self,
+        event: &str,
+        ply: usize,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        game: &Game,
+    ) {
+        self.nodes += 1;
+        if self.is_traced(ply) {
+            self.write(format_args!(
+                "{} ply={} depth={} alpha={} beta={} fen={}",
+                event,
+                ply,
+                depth,
+                alpha,
+                beta,
+                game.to_fen()
+            ));
+        }
+    }
+
+    pub fn quiescence(
+        &mut self,
+        ply: usize,
+        alpha: i32,
+        beta: i32,
+        standing_pat: i32,
+        game: &Game,
+    ) {
+        self.nodes += 1;
+        if self.is_traced(ply) {
+            self.write(format_args!(
+                "quiescence ply={} alpha={} beta={} standing_pat={} fen={}",
+                ply,
+                alpha,
+                beta,
+                standing_pat,
+                game.to_fen()
+            ));
+        }
+    }
+
+    pub fn transposition_table_hit(&mut self, ply: usize, entry: &TranspositionTableEntry) {
+        if self.is_traced(ply) {
+            self.write(format_args!(
+                "tt ply={} depth={} flag={:?} score={}",
+                ply, entry.depth, entry.flag, entry.score
+            ));
+        }
+    }
+
+    pub fn endgame_table_hit(&mut self, ply: usize, score: i32) {
+        if self.is_traced(ply) {
+            self.write(format_args!("db ply={} score={}", ply, score));
+        }
+    }
+
+    /// A move that was searched, with its score from the perspective of the side that made it.
+    pub fn move_searched(&mut self, ply: usize, m: &MoveSequence, score: i32) {
+        if self.is_traced(ply) {
+            self.write(format_args!(
+                "move ply={} move={} score={}",
+                ply,
+                m.to_string(),
+                score
+            ));
+        }
+    }
+
+    pub fn cutoff(&mut self, ply: usize, m: &MoveSequence, score: i32) {
+        if self.is_traced(ply) {
+            self.write(format_args!(
+                "cutoff ply={} move={} score={}",
+                ply,
+                m.to_string(),
+                score
+            ));
+        }
+    }
+
+    pub fn finish(&mut self) {
+        let _ = self.writer.flush();
+    }
+
+    fn is_within_node_limit(&self) -> bool {
+        self.max_nodes
+            .is_none_or(|max_nodes| self.nodes <= max_nodes)
+    }
+
+    fn is_traced(&self, ply: usize) -> bool {
+        self.is_within_node_limit() && self.max_ply.is_none_or(|max_ply| ply <= max_ply)
+    }
+
+    fn limit(limit: Option<impl Display>) -> String {
+        limit.map_or("-".to_string(), |limit| limit.to_string())
+    }
+
+    fn write(&mut self, line: std::fmt::Arguments) {
+        // A trace that can not be written should not stop the search.
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod search_tracer_tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn trace_respects_the_limits() {
+        let path = std::env::temp_dir().join("checkers_search_tracer_test.txt");
+        let path = path.to_str().unwrap();
+
+        let mut game = Game::from_fen("B:WK32:BK18,K19,K27").unwrap();
+        let mut engine = Engine::with_endgame_table(Duration::MAX, EndgameTable::new());
+        engine.trace_to_file(path, Some(2), Some(500)).unwrap();
+        engine.search_to_depth(&mut game, 6);
+        engine.stop_tracing();
+
+        let trace = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(trace.starts_with("search fen=B:WK32:BK18,K19,K27 depth=6"));
+        assert!(trace
+            .lines()
+            .any(|line| line.starts_with("root ply=0 depth=1")));
+        assert!(trace.lines().any(|line| line.starts_with("move ply=0")));
+
+        let nodes = trace
+            .lines()
+            .filter(|line| line.starts_with("root") || line.starts_with("node"))
+            .count();
+        assert!(nodes > 0 && nodes <= 500);
+
+        for line in trace.lines() {
+            if let Some(ply) = line.split(' ').find_map(|field| field.strip_prefix("ply=")) {
+                assert!(ply.parse::<usize>().unwrap() <= 2, "{}", line);
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+/// How strong the `Engine` plays. Weaker levels search less, see positions through a noisy
+/// evaluation, and sometimes play the second best move if it is not much worse than the best.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Strength {
+    /// Deepest iteration of a search.
+    pub max_depth: Option<usize>,
+    /// Most nodes of a search.
+    pub max_nodes: Option<usize>,
+    /// Largest change of the evaluation of a position, in either direction.
+    /// Every position keeps its noise for the whole search.
+    pub evaluation_noise: i32,
+    /// Chance in percent to play the second best move of a multi-PV search.
+    pub second_best_chance: u32,
+    /// Most the second best move may score below the best move to be played.
+    pub second_best_margin: i32,
+}
+
+impl Default for Strength {
+    fn default() -> Self {
+        Strength::level(Strength::MAX_LEVEL)
+    }
+}
+
+impl Strength {
+    /// The full strength of the engine.
+    pub const MAX_LEVEL: usize = 10;
+    /// Largest `evaluation_noise`, so a noisy evaluation never looks like a win.
+    pub const MAX_EVALUATION_NOISE: i32 = 5_000;
+
+    /// Strength levels from 1, for beginners, to `MAX_LEVEL`, without any handicap.
+    /// Levels above `MAX_LEVEL` are full strength as well.
+    ///
+    /// Approximate Elo, measured by `ladder` in self-play at 100ms a move without the
+    /// endgame database, with 14 games between neighbouring levels and level 1 anchored at 0.
+    /// Few games per pair, so the differences are rough:
+    ///
+    /// | Level | Elo  |
+    /// |-------|------|
+    /// | 1     | 0    |
+    /// | 2     | 76   |
+    /// | 3     | 100  |
+    /// | 4     | 291  |
+    /// | 5     | 482  |
+    /// | 6     | 747  |
+    /// | 7     | 877  |
+    /// | 8     | 1007 |
+    /// | 9     | 1233 |
+    /// | 10    | 1335 |
+    pub fn level(level: usize) -> Self {
+        let (max_depth, max_nodes, evaluation_noise, second_best_chance, second_best_margin) =
+            match level {
+                0 | 1 => (Some(1), Some(50), 1500, 50, 2000),
+                2 => (Some(2), Some(200), 1000, 40, 1500),
+                3 => (Some(3), Some(500), 800, 30, 1000),
+                4 => (Some(4), Some(1_000), 600, 25, 800),
+                5 => (Some(5), Some(2_000), 400, 20, 600),
+                6 => (Some(6), Some(5_000), 300, 15, 400),
+                7 => (Some(8), Some(10_000), 200, 10, 300),
+                8 => (Some(10), Some(30_000), 100, 5, 200),
+                9 => (None, Some(100_000), 50, 0, 0),
+                _ => (None, None, 0, 0, 0),
+            };
+
+        Strength {
+            max_depth,
+            max_nodes,
+            evaluation_noise,
+            second_best_chance,
+            second_best_margin,
+        }
+    }
+
+    /// Check that a strength that is not one of the levels makes sense.
+    pub fn validate(&self) -> Result<(), String> {
+        let invalid = |field: &str, value: String, expected: &str| {
+            Err(format!(
+                "invalid strength: {} {}, expected {}",
+                field, value, expected
+            ))
+        };
+
+        if self.max_depth == Some(0) {
+            return invalid("max_depth", "0".to_string(), "at least 1");
+        }
+        if self.max_nodes == Some(0) {
+            return invalid("max_nodes", "0".to_string(), "at least 1");
+        }
+        if !(0..=Strength::MAX_EVALUATION_NOISE).contains(&self.evaluation_noise) {
+            return invalid(
+                "evaluation_noise",
+                self.evaluation_noise.to_string(),
+                &format!("a number from 0 to {}", Strength::MAX_EVALUATION_NOISE),
+            );
+        }
+        if self.second_best_chance > 100 {
+            return invalid(
+                "second_best_chance",
+                self.second_best_chance.to_string(),
+                "a number from 0 to 100",
+            );
+        }
+        if self.second_best_margin < 0 {
+            return invalid(
+                "second_best_margin",
+                self.second_best_margin.to_string(),
+                "at least 0",
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Number of best moves a search needs to find, to choose between them.
+    pub fn multi_pv(&self) -> usize {
+        match self.second_best_chance {
+            0 => 1,
+            _ => 2,
+        }
+    }
+
+    /// The noise of the position with the hash `key`, the same for every evaluation
+    /// with the same `seed`.
+    pub fn noise(&self, key: u64, seed: u64) -> i32 {
+        if self.evaluation_noise <= 0 {
+            return 0;
+        }
+
+        // SplitMix64, so similar keys get unrelated noise.
+        let mut x = (key ^ seed).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+
+        (x % (2 * self.evaluation_noise as u64 + 1)) as i32 - self.evaluation_noise
+    }
+}
+
+#[cfg(test)]
+mod strength_tests {
+    use super::*;
+
+    #[test]
+    fn every_level_is_valid() {
+        for level in 1..=Strength::MAX_LEVEL {
+            assert_eq!(Strength::level(level).validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn noise_stays_within_the_limit() {
+        let strength = Strength::level(1);
+        let noise = (0..1_000)
+            .map(|key| strength.noise(key, 7))
+            .collect::<Vec<i32>>();
+
+        assert!(noise.iter().all(|noise| noise.abs() <= 1500));
+        assert!(noise.iter().any(|&noise| noise > 1000));
+        assert!(noise.iter().any(|&noise| noise < -1000));
+        // The same position and seed always get the same noise, another seed gets another.
+        assert_eq!(strength.noise(42, 7), noise[42]);
+        assert_ne!(strength.noise(42, 8), noise[42]);
+        assert_eq!(Strength::default().noise(42, 7), 0);
+    }
+}
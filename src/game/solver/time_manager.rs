@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// Number of moves the remaining clock time is split over, if the time control does not say.
+const DEFAULT_MOVES_TO_GO: usize = 25;
+/// Time kept back for the overhead of sending the move.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(20);
+/// Growth of the iteration time with every extra ply, until it is measured.
+const DEFAULT_BRANCHING_FACTOR: f64 = 4.0;
+/// A score drop of this much between iterations extends the time for the move.
+const SCORE_DROP_MARGIN: i32 = 200;
+/// Factor the time for the move is extended by, for each reason to extend it.
+const TIME_EXTENSION: f64 = 1.5;
+
+/// Decides how long a search may take.
+///
+/// A search has an optimum time, after which no new iteration is started, and a maximum time,
+/// after which it is stopped. Before every iteration the time it will take is predicted from
+/// the branching factor of the last iterations, an iteration that would not finish within
+/// the optimum time is not started.
+/// The optimum time is extended if the best move changed or the score dropped,
+/// because the search is not settled yet.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start_time: Instant,
+    optimum_time: Option<Duration>,
+    maximum_time: Option<Duration>,
+    iteration_start: Duration,
+    last_iteration_time: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits) -> Self {
+        let (mut optimum_time, mut maximum_time) = match (&limits.clock, limits.infinite) {
+            (Some(clock), false) => {
+                let remaining = clock.remaining.saturating_sub(MOVE_OVERHEAD);
+                let moves_to_go = clock.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) as u32;
+
+                let optimum = (remaining / moves_to_go + clock.increment * 3 / 4).min(remaining);
+                let maximum = (optimum * 3).min(remaining / 2).max(optimum);
+
+                (Some(optimum), Some(maximum))
+            }
+            _ => (None, None),
+        };
+
+        if let (Some(move_time), false) = (limits.move_time, limits.infinite) {
+            optimum_time = Some(optimum_time.map_or(move_time, |time| time.min(move_time)));
+            maximum_time = Some(maximum_time.map_or(move_time, |time| time.min(move_time)));
+        }
+
+        TimeManager {
+            start_time: Instant::now(),
+            optimum_time,
+            maximum_time,
+            iteration_start: Duration::ZERO,
+            last_iteration_time: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Time the search should take, `None` if it is not limited by time.
+    pub fn optimum_time(&self) -> Option<Duration> {
+        self.optimum_time
+    }
+
+    /// Time after which the search is stopped, `None` if it is not limited by time.
+    pub fn maximum_time(&self) -> Option<Duration> {
+        self.maximum_time
+    }
+
+    /// Whether the search has to be stopped.
+    pub fn is_time_up(&self) -> bool {
+        self.maximum_time
+            .is_some_and(|maximum_time| self.elapsed() >= maximum_time)
+    }
+
+    /// Called after every finished iteration. Returns whether the next iteration is expected
+    /// to finish in time. `score_drop` is how much worse the score got during the iteration.
+    pub fn start_next_iteration(&mut self, best_move_changed: bool, score_drop: i32) -> bool {
+        let now = self.elapsed();
+        let iteration_time = now - self.iteration_start;
+
+        let branching_factor = match self.last_iteration_time {
+            Some(last_iteration_time) if last_iteration_time >= Duration::from_millis(1) => {
+                (iteration_time.as_secs_f64() / last_iteration_time.as_secs_f64()).clamp(1.5, 8.0)
+            }
+            _ => DEFAULT_BRANCHING_FACTOR,
+        };
+
+        self.last_iteration_time = Some(iteration_time);
+        self.iteration_start = now;
+
+        let Some(optimum_time) = self.optimum_time else {
+            return true;
+        };
+
+        let mut budget = optimum_time.as_secs_f64();
+        if best_move_changed {
+            budget *= TIME_EXTENSION;
+        }
+        if score_drop > SCORE_DROP_MARGIN {
+            budget *= TIME_EXTENSION;
+        }
+        if let Some(maximum_time) = self.maximum_time {
+            budget = budget.min(maximum_time.as_secs_f64());
+        }
+
+        now.as_secs_f64() + iteration_time.as_secs_f64() * branching_factor <= budget
+    }
+}
+
+#[cfg(test)]
+mod time_manager_tests {
+    use super::*;
+
+    #[test]
+    fn clock_time_is_split_over_the_moves_to_go() {
+        let limits = SearchLimits::clock(Duration::from_secs(10), Duration::ZERO, Some(10));
+        let time_manager = TimeManager::new(&limits);
+
+        let optimum_time = time_manager.optimum_time().unwrap();
+        assert!(optimum_time <= Duration::from_secs(1));
+        assert!(optimum_time > Duration::from_millis(900));
+        assert!(time_manager.maximum_time.unwrap() >= optimum_time);
+        assert!(time_manager.maximum_time.unwrap() <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn last_move_before_the_time_control_uses_the_remaining_time() {
+        let limits = SearchLimits::clock(Duration::from_secs(1), Duration::ZERO, Some(1));
+        let time_manager = TimeManager::new(&limits);
+
+        assert_eq!(time_manager.optimum_time(), time_manager.maximum_time);
+        assert!(time_manager.maximum_time.unwrap() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn unstable_searches_get_more_time() {
+        // 100ms optimum time and 300ms maximum time.
+        let limits = SearchLimits::clock(Duration::from_millis(3020), Duration::ZERO, Some(30));
+        let time_manager = TimeManager::new(&limits);
+        assert_eq!(
+            time_manager.optimum_time(),
+            Some(Duration::from_millis(100))
+        );
+
+        std::thread::sleep(Duration::from_millis(25));
+
+        // The next iteration is predicted to take four times as long as the first one,
+        // which ends after the optimum time, but within the extended one.
+        assert!(!time_manager.clone().start_next_iteration(false, 0));
+        assert!(time_manager.clone().start_next_iteration(true, 1000));
+    }
+
+    #[test]
+    fn infinite_searches_are_not_limited_by_time() {
+        let limits = SearchLimits {
+            infinite: true,
+            ..SearchLimits::move_time(Duration::ZERO)
+        };
+        let mut time_manager = TimeManager::new(&limits);
+
+        assert!(!time_manager.is_time_up());
+        assert!(time_manager.start_next_iteration(false, 0));
+    }
+}
@@ -1,3 +1,9 @@
+//! Self-play between two players of the `PlayerRegistry`.
+//!
+//! Usage: checkers_checkerboard [BLACK] [WHITE] [GAMES] [MOVE_TIME_MS]
+//!
+//! Both players default to `engine`, 9 games are played with 50ms a move.
+
 #![allow(unused)]
 
 pub mod game;
@@ -8,78 +14,90 @@ use std::time::Duration;
 
 use crate::game::*;
 
-#[tokio::main]
-async fn main() {
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let black = args.first().map_or("engine", |arg| arg.as_str());
+    let white = args.get(1).map_or("engine", |arg| arg.as_str());
+    let games = args
+        .get(2)
+        .map_or(9, |arg| arg.parse().expect("invalid number of games"));
+    let move_time = args
+        .get(3)
+        .map_or(50, |arg| arg.parse().expect("invalid move time"));
+
+    let registry = PlayerRegistry::default();
+    let limits = SearchLimits::move_time(Duration::from_millis(move_time));
+
     let mut black_wins = 0;
     let mut white_wins = 0;
     let mut draws = 0;
 
-    for _ in 0..9 {
+    for _ in 0..games {
         let mut game = Game::new();
-        let mut black_engine = Engine::new(Color::Black, Duration::from_millis(50));
-        let mut white_engine = Engine::new(Color::White, Duration::from_millis(10));
-
-        // let (best_move, score, pv) = black_engine.find_best_move(&mut game).await;
-        // println!("Best move: {:?} <{}> {} ", best_move, score, pv);
+        let mut black_player = registry
+            .create(black)
+            .unwrap_or_else(|error| panic!("{}", error));
+        let mut white_player = registry
+            .create(white)
+            .unwrap_or_else(|error| panic!("{}", error));
 
         loop {
-            let black_move = black_engine.find_best_move(&mut game).await;
-            game.make_move_sequence(&black_move.0.clone().unwrap());
-            println!(
-                "B <{}> ({}) {}",
-                black_engine.current_depth - 1,
-                Engine::translate_score(black_move.1, Color::Black, game.ply),
-                black_move.2
-            );
-
-            println!("{}", game.to_console_string());
-
-            if game.is_draw() {
-                println!("Game over! Draw!");
-                draws += 1;
-                println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
-                break;
-            } else if game.is_black_win() {
-                println!("Game over! Black won!");
-                black_wins += 1;
-                println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
-                break;
-            } else if game.is_white_win() {
-                println!("Game over! White won!");
-                white_wins += 1;
-                println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
-                break;
+            match game.result() {
+                Some(GameResult::Draw) => {
+                    println!("Game over! Draw!");
+                    draws += 1;
+                    break;
+                }
+                Some(GameResult::BlackWin) => {
+                    println!("Game over! Black won!");
+                    black_wins += 1;
+                    break;
+                }
+                Some(GameResult::WhiteWin) => {
+                    println!("Game over! White won!");
+                    white_wins += 1;
+                    break;
+                }
+                None => {}
             }
 
-            let white_move = white_engine.find_best_move(&mut game).await;
-            game.make_move_sequence(&white_move.0.clone().unwrap());
+            let (side, player) = match game.side_to_move {
+                Color::Black => ("B", &mut black_player),
+                Color::White => ("W", &mut white_player),
+            };
+            let result = player.solve(&game, &limits);
+            let white_score = match game.side_to_move {
+                Color::Black => -result.score,
+                Color::White => result.score,
+            };
+
             println!(
-                "W <{}> ({}) {}",
-                white_engine.current_depth - 1,
-                Engine::translate_score(white_move.1, Color::White, game.ply),
-                white_move.2
+                "{} {} ({}) {} [nodes: {}]",
+                side,
+                player.name(),
+                Engine::translate_score(white_score, game.side_to_move.clone()),
+                result
+                    .principal_variation
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                result.nodes
             );
 
-            println!("{}", game.to_console_string());
-
-            if game.is_draw() {
-                println!("Game over! Draw!");
-                draws += 1;
-                println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
+            let Some(best_move) = &result.best_move else {
+                println!("{} found no move, the game is abandoned!", player.name());
                 break;
-            } else if game.is_black_win() {
-                println!("Game over! Black won!");
-                black_wins += 1;
-                println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
-                break;
-            } else if game.is_white_win() {
-                println!("Game over! White won!");
-                white_wins += 1;
-                println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
-                break;
-            }
+            };
+            game.make_move_sequence(best_move);
+            println!("{}", game.to_console_string());
         }
+
+        println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
     }
 
-    println!("stats: b: {} w: {} d: {}", black_wins, white_wins, draws);
+    println!(
+        "{} (black) vs {} (white): b: {} w: {} d: {}",
+        black, white, black_wins, white_wins, draws
+    );
 }
@@ -1,8 +1,12 @@
-use std::{collections::HashMap, vec};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+    vec,
+};
 
 use super::game::*;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TranspositionTableFlag {
     Exact,
     LowerBound,
@@ -10,13 +14,97 @@ pub enum TranspositionTableFlag {
     Unknown,
 }
 
-#[derive(Debug, Clone)]
+impl TranspositionTableFlag {
+    fn to_bits(self) -> u64 {
+        match self {
+            TranspositionTableFlag::Unknown => 0,
+            TranspositionTableFlag::Exact => 1,
+            TranspositionTableFlag::LowerBound => 2,
+            TranspositionTableFlag::UpperBound => 3,
+        }
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        match bits {
+            1 => TranspositionTableFlag::Exact,
+            2 => TranspositionTableFlag::LowerBound,
+            3 => TranspositionTableFlag::UpperBound,
+            _ => TranspositionTableFlag::Unknown,
+        }
+    }
+}
+
+/// A move sequence packed into 24 bits: the start tile, the number of steps and the direction
+/// of the first `PackedMove::MAX_STEPS` steps. That is the path of the piece, which tells the
+/// move sequences of a position apart, unlike their start, end and captured tiles.
+/// It does not hold enough to make the move, but it identifies it among the
+/// move sequences generated in the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedMove(u32);
+
+impl PackedMove {
+    pub const NONE: PackedMove = PackedMove(0);
+    /// Steps whose direction fits into the packed move, longer paths are only
+    /// told apart by their first steps.
+    const MAX_STEPS: usize = 7;
+
+    pub fn new(move_sequence: &MoveSequence) -> Self {
+        let moves = move_sequence.moves();
+
+        let Some(first) = moves.first() else {
+            return PackedMove::NONE;
+        };
+
+        let directions = moves
+            .iter()
+            .take(PackedMove::MAX_STEPS)
+            .enumerate()
+            .fold(0, |directions, (i, m)| {
+                directions | PackedMove::direction(m) << (2 * i)
+            });
+        let steps = moves.len().min(15) as u32;
+
+        PackedMove(first.from as u32 | steps << 6 | directions << 10)
+    }
+
+    /// One of the four diagonals a step goes along, in two bits.
+    fn direction(m: &Move) -> u32 {
+        let forward = m.to > m.from;
+        // Steps along one diagonal move by a multiple of 5 tiles, along the other one by 4.
+        let diagonal = m.to.abs_diff(m.from).is_multiple_of(5);
+
+        (forward as u32) << 1 | diagonal as u32
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == PackedMove::NONE
+    }
+
+    /// Find the packed move among the move sequences of the position it was stored for.
+    /// `None` if a path too long for the packed move leaves more than one candidate.
+    pub fn find<'a>(&self, move_sequences: &'a [MoveSequence]) -> Option<&'a MoveSequence> {
+        if self.is_none() {
+            return None;
+        }
+
+        let mut candidates = move_sequences
+            .iter()
+            .filter(|move_sequence| PackedMove::new(move_sequence) == *self);
+
+        let move_sequence = candidates.next()?;
+        candidates.next().is_none().then_some(move_sequence)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TranspositionTableEntry {
     pub key: u64,
-    pub best_move_sequence: MoveSequence,
+    pub best_move: PackedMove,
     pub score: i32,
     pub depth: usize,
     pub flag: TranspositionTableFlag,
+    /// The search the entry was stored in, set by the table on insertion.
+    pub generation: u8,
 }
 
 impl TranspositionTableEntry {
@@ -46,31 +134,67 @@ impl TranspositionTableEntry {
     ) -> Self {
         TranspositionTableEntry {
             key,
-            best_move_sequence,
+            best_move: PackedMove::new(&best_move_sequence),
             score,
             depth,
             flag,
+            generation: 0,
         }
     }
 
     pub fn create_empty_with_key(key: u64) -> Self {
         TranspositionTableEntry {
             key,
-            best_move_sequence: MoveSequence::new(vec![]),
+            best_move: PackedMove::NONE,
             score: Score::DRAW,
             depth: 0,
             flag: TranspositionTableFlag::Unknown,
+            generation: 0,
         }
     }
 }
 
-#[derive(Debug)]
+/// Bit layout of the data word of an entry.
+impl TranspositionTableEntry {
+    const SCORE_BITS: u64 = 24;
+    const DEPTH_SHIFT: u64 = 24;
+    const FLAG_SHIFT: u64 = 32;
+    const GENERATION_SHIFT: u64 = 34;
+    const MOVE_SHIFT: u64 = 40;
+
+    fn pack(&self, generation: u8) -> u64 {
+        let score = self.score as u64 & ((1 << TranspositionTableEntry::SCORE_BITS) - 1);
+
+        score
+            | (self.depth.min(u8::MAX as usize) as u64) << TranspositionTableEntry::DEPTH_SHIFT
+            | self.flag.to_bits() << TranspositionTableEntry::FLAG_SHIFT
+            | ((generation & GENERATION_MASK) as u64) << TranspositionTableEntry::GENERATION_SHIFT
+            | (self.best_move.0 as u64) << TranspositionTableEntry::MOVE_SHIFT
+    }
+
+    fn unpack(key: u64, data: u64) -> Self {
+        // Sign extend the score.
+        let score = ((data << (64 - TranspositionTableEntry::SCORE_BITS)) as i64
+            >> (64 - TranspositionTableEntry::SCORE_BITS)) as i32;
+
+        TranspositionTableEntry {
+            key,
+            best_move: PackedMove((data >> TranspositionTableEntry::MOVE_SHIFT) as u32),
+            score,
+            depth: (data >> TranspositionTableEntry::DEPTH_SHIFT) as u8 as usize,
+            flag: TranspositionTableFlag::from_bits(
+                (data >> TranspositionTableEntry::FLAG_SHIFT) & 0b11,
+            ),
+            generation: (data >> TranspositionTableEntry::GENERATION_SHIFT) as u8 & GENERATION_MASK,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct TranspositionTableHashMap([u64; 64]);
 
 impl TranspositionTableHashMap {
-    pub fn new() -> Self {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    pub fn new(rng: &mut impl rand::Rng) -> Self {
         let mut array = [0; 64];
 
         for i in 0..64 {
@@ -81,17 +205,9 @@ impl TranspositionTableHashMap {
     }
 }
 
-impl Default for TranspositionTableHashMap {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[derive(Debug)]
-pub struct TranspositionTable {
-    table_size: usize,
-    table: Vec<Option<TranspositionTableEntry>>,
-
+/// Random keys used to hash game states.
+#[derive(Debug, Clone)]
+pub struct TranspositionTableHasher {
     white_hashmap: TranspositionTableHashMap,
     black_hashmap: TranspositionTableHashMap,
     white_kings_hashmap: TranspositionTableHashMap,
@@ -100,25 +216,26 @@ pub struct TranspositionTable {
     // draw_hash: u64,
 }
 
-impl TranspositionTable {
-    pub fn new(table_size: usize) -> Self {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+impl TranspositionTableHasher {
+    pub fn new() -> Self {
+        Self::from_rng(&mut rand::thread_rng())
+    }
+
+    /// Keys generated from a seed, the same seed always gives the same keys.
+    pub fn with_seed(seed: u64) -> Self {
+        use rand::SeedableRng;
+        Self::from_rng(&mut rand::rngs::StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(rng: &mut impl rand::Rng) -> Self {
         let side_hash = rng.gen();
         // let draw_hash = rng.gen();
 
-        let white_hashmap = TranspositionTableHashMap::default();
-        let black_hashmap = TranspositionTableHashMap::default();
-        let white_kings_hashmap = TranspositionTableHashMap::default();
-        let black_kings_hashmap = TranspositionTableHashMap::default();
-
-        let mut tt = TranspositionTable {
-            table_size,
-            table: vec![None; table_size],
-            white_hashmap,
-            black_hashmap,
-            white_kings_hashmap,
-            black_kings_hashmap,
+        let mut hasher = TranspositionTableHasher {
+            white_hashmap: TranspositionTableHashMap::new(rng),
+            black_hashmap: TranspositionTableHashMap::new(rng),
+            white_kings_hashmap: TranspositionTableHashMap::new(rng),
+            black_kings_hashmap: TranspositionTableHashMap::new(rng),
             side_hash,
         };
 
@@ -128,85 +245,239 @@ impl TranspositionTable {
         // This means that the hash function is not one-to-one, which means that it's not
         // possible to get the original value from the hash.
 
-        while !tt.assert_no_collisions() {
-            let white_hashmap = TranspositionTableHashMap::default();
-            let black_hashmap = TranspositionTableHashMap::default();
-            let white_kings_hashmap = TranspositionTableHashMap::default();
-            let black_kings_hashmap = TranspositionTableHashMap::default();
-
-            tt = TranspositionTable {
-                table_size,
-                table: vec![None; table_size],
-                white_hashmap,
-                black_hashmap,
-                white_kings_hashmap,
-                black_kings_hashmap,
+        while !hasher.assert_no_collisions() {
+            hasher = TranspositionTableHasher {
+                white_hashmap: TranspositionTableHashMap::new(rng),
+                black_hashmap: TranspositionTableHashMap::new(rng),
+                white_kings_hashmap: TranspositionTableHashMap::new(rng),
+                black_kings_hashmap: TranspositionTableHashMap::new(rng),
                 side_hash,
             };
         }
 
-        tt
+        hasher
     }
 }
 
-/// Insertion and retrieval of entries in the transposition table.
+impl Default for TranspositionTableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generations are counted modulo 64.
+const GENERATION_MASK: u8 = 0b11_1111;
+const BUCKET_SIZE: usize = 4;
+
+/// One entry of a bucket. The key word holds the key xor'ed with the data word,
+/// so an entry that is torn by a concurrent write fails the key check instead of
+/// returning mixed up data.
+#[derive(Debug, Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key.load(Ordering::Relaxed) ^ data;
+        (key, data)
+    }
+
+    fn store(&self, key: u64, data: u64) {
+        self.key.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Entries whose keys map to the same index, sized to fill one cache line.
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct Bucket([Slot; BUCKET_SIZE]);
+
+/// Lock-free table that is shared between search threads.
+///
+/// Entries are replaced by depth and age: entries of the current search are only
+/// replaced by deeper searches of the same position, otherwise the shallowest and
+/// oldest entry of the bucket makes room.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    generation: AtomicU8,
+    hasher: TranspositionTableHasher,
+    /// Seed of the hash keys, `None` if they are random.
+    seed: Option<u64>,
+}
+
 impl TranspositionTable {
-    pub fn insert(&mut self, entry: TranspositionTableEntry) {
-        // Before insertion we should check wether it's worth overwriting the entry.
-        // If the entry is deeper than the one we're trying to insert, we should
-        // not overwrite it.
-        if let Some(existing_entry) = self.fetch(entry.key) {
-            if existing_entry.depth > entry.depth {
-                return;
-            }
+    /// Create a table that takes up about `megabytes` of memory.
+    pub fn new(megabytes: usize) -> Self {
+        Self::with_hasher(megabytes, TranspositionTableHasher::new(), None)
+    }
+
+    /// Create a table with hash keys generated from `seed`,
+    /// so positions are stored in the same places on every run.
+    pub fn with_seed(megabytes: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            megabytes,
+            TranspositionTableHasher::with_seed(seed),
+            Some(seed),
+        )
+    }
+
+    fn with_hasher(megabytes: usize, hasher: TranspositionTableHasher, seed: Option<u64>) -> Self {
+        let bucket_count = (megabytes * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+
+        TranspositionTable {
+            buckets: (0..bucket_count).map(|_| Bucket::default()).collect(),
+            generation: AtomicU8::new(0),
+            hasher,
+            seed,
         }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
 
-        let index = (entry.key % self.table_size as u64) as usize;
+    /// Size of the table in megabytes.
+    pub fn size_in_megabytes(&self) -> usize {
+        self.buckets.len() * std::mem::size_of::<Bucket>() / (1024 * 1024)
+    }
 
-        self.table[index] = Some(entry);
+    /// Start a new search. Entries of earlier searches are preferred for replacement.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn fetch(&self, key: u64) -> Option<&TranspositionTableEntry> {
-        // return None;
-        let index = (key % self.table_size as u64) as usize;
+    /// Permille of the entries that belong to the current search, sampled from the first buckets.
+    pub fn fill_permille(&self) -> usize {
+        let generation = self.generation();
+        let buckets = &self.buckets[..self.buckets.len().min(1000 / BUCKET_SIZE)];
+
+        let used = buckets
+            .iter()
+            .flat_map(|bucket| bucket.0.iter())
+            .filter(|slot| {
+                let (key, data) = slot.load();
+                data != 0 && TranspositionTableEntry::unpack(key, data).generation == generation
+            })
+            .count();
+
+        used * 1000 / (buckets.len() * BUCKET_SIZE)
+    }
 
-        if let Some(entry) = &self.table[index] {
-            if entry.key == key {
-                return Some(entry);
-            } else {
-                return None;
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed) & GENERATION_MASK
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        &self.buckets[(key % self.buckets.len() as u64) as usize]
+    }
+}
+
+/// Insertion and retrieval of entries in the transposition table.
+impl TranspositionTable {
+    pub fn insert(&self, entry: TranspositionTableEntry) {
+        let generation = self.generation();
+        let bucket = self.bucket(entry.key);
+
+        let mut replace = &bucket.0[0];
+        let mut replace_priority = i32::MAX;
+
+        for slot in bucket.0.iter() {
+            let (key, data) = slot.load();
+
+            if data == 0 {
+                if replace_priority > i32::MIN {
+                    replace = slot;
+                    replace_priority = i32::MIN;
+                }
+                continue;
+            }
+
+            let existing_entry = TranspositionTableEntry::unpack(key, data);
+
+            if key == entry.key {
+                // Keep deeper results of the current search.
+                if existing_entry.generation == generation && existing_entry.depth > entry.depth {
+                    return;
+                }
+
+                replace = slot;
+                break;
+            }
+
+            let age = generation.wrapping_sub(existing_entry.generation) & GENERATION_MASK;
+            let priority = existing_entry.depth as i32 - 8 * age as i32;
+            if priority < replace_priority {
+                replace = slot;
+                replace_priority = priority;
             }
         }
 
-        None
+        replace.store(entry.key, entry.pack(generation));
     }
 
-    pub fn get_principal_variation_line(&self, key: u64) -> Vec<MoveSequence> {
-        let mut line = vec![];
+    pub fn fetch(&self, key: u64) -> Option<TranspositionTableEntry> {
+        self.bucket(key).0.iter().find_map(|slot| {
+            let (slot_key, data) = slot.load();
 
-        let mut current_key = key;
+            if data != 0 && slot_key == key {
+                Some(TranspositionTableEntry::unpack(key, data))
+            } else {
+                None
+            }
+        })
+    }
 
-        while let Some(entry) = self.fetch(current_key) {
-            if line
-                .iter()
-                .any(|line_entry: &TranspositionTableEntry| line_entry.key == entry.key)
-            {
+    /// Follow the best moves stored in the table, starting at the current position.
+    pub fn get_principal_variation_line(&self, game: &mut Game) -> Vec<MoveSequence> {
+        let mut line = vec![];
+        let mut keys = vec![];
+
+        while let Some(entry) = self.fetch(self.hash(game)) {
+            if keys.contains(&entry.key) {
                 break;
             }
 
-            line.push(entry.clone());
+            let move_sequences = game.generate_move_sequences();
+            let Some(move_sequence) = entry.best_move.find(&move_sequences) else {
+                break;
+            };
 
-            current_key = self.hash_move_sequence(current_key, &entry.best_move_sequence, true);
+            keys.push(entry.key);
+            line.push(move_sequence.clone());
+            game.make_move_sequence(move_sequence);
         }
 
-        line.iter()
-            .map(|entry| entry.best_move_sequence.clone())
-            .collect()
+        for _ in line.iter() {
+            game.unmake_move_sequence();
+        }
+
+        line
     }
 }
 
 /// Hashing of game states.
 impl TranspositionTable {
+    pub fn hash(&self, game: &Game) -> u64 {
+        self.hasher.hash(game)
+    }
+
+    pub fn hash_move_sequence(
+        &self,
+        key: u64,
+        move_sequence: &MoveSequence,
+        is_side_switch: bool,
+    ) -> u64 {
+        self.hasher
+            .hash_move_sequence(key, move_sequence, is_side_switch)
+    }
+}
+
+impl TranspositionTableHasher {
     pub fn hash(&self, game: &Game) -> u64 {
         let mut hash = 0;
 
@@ -299,7 +570,7 @@ impl TranspositionTable {
     }
 }
 
-impl TranspositionTable {
+impl TranspositionTableHasher {
     fn assert_no_collisions(&self) -> bool {
         let mut key_map = HashMap::new();
 
@@ -345,8 +616,84 @@ impl TranspositionTable {
 
 impl Default for TranspositionTable {
     fn default() -> Self {
-        // 2^20 + 7 to make it a prime number
-        // Will result in a table size of 2^20 * 48 bytes = 50.3 MB
-        Self::new(1_048_583)
+        Self::new(EngineConfig::default().transposition_table_size)
+    }
+}
+
+#[cfg(test)]
+mod transposition_table_tests {
+    use super::*;
+
+    #[test]
+    fn entries_survive_packing() {
+        let mut game = Game::new();
+        let move_sequences = game.generate_move_sequences();
+        let table = TranspositionTable::new(1);
+
+        for (i, m) in move_sequences.iter().enumerate() {
+            let score = -Score::WIN + i as i32;
+            table.insert(TranspositionTableEntry::create_with_key(
+                i as u64,
+                m.clone(),
+                score,
+                i,
+                TranspositionTableFlag::UpperBound,
+            ));
+
+            let entry = table.fetch(i as u64).unwrap();
+            assert_eq!(entry.best_move.find(&move_sequences), Some(m));
+            assert_eq!(entry.score, score);
+            assert_eq!(entry.depth, i);
+            assert_eq!(entry.flag, TranspositionTableFlag::UpperBound);
+        }
+    }
+
+    #[test]
+    fn captures_with_the_same_start_and_end_are_told_apart() {
+        let mut game = Game::from_fen("W:WK10,K18:B6,7,8,11,14,16,22,23,24,25").unwrap();
+        let move_sequences = game.generate_move_sequences();
+
+        for m in ["10x3x12x19x28", "10x17x26x19x28"] {
+            let m = move_sequences
+                .iter()
+                .find(|move_sequence| move_sequence.to_string() == m)
+                .unwrap();
+            assert_eq!(PackedMove::new(m).find(&move_sequences), Some(m));
+        }
+    }
+
+    #[test]
+    fn old_entries_are_replaced_first() {
+        let mut game = Game::new();
+        let m = game.generate_move_sequences().remove(0);
+        // A single bucket.
+        let table = TranspositionTable::new(0);
+        let insert = |key: u64, depth: usize| {
+            table.insert(TranspositionTableEntry::create_with_key(
+                key,
+                m.clone(),
+                Score::DRAW,
+                depth,
+                TranspositionTableFlag::Exact,
+            ))
+        };
+
+        for key in 0..BUCKET_SIZE as u64 {
+            insert(key, 10);
+        }
+
+        // Deep entries of the last search make room before shallow entries of this search.
+        table.new_search();
+        insert(100, 4);
+        insert(200, 4);
+
+        assert!(table.fetch(0).is_none());
+        assert!(table.fetch(1).is_none());
+        assert_eq!(table.fetch(100).unwrap().depth, 4);
+        assert_eq!(table.fetch(200).unwrap().depth, 4);
+
+        // A shallower search of the same position does not replace the entry.
+        insert(100, 2);
+        assert_eq!(table.fetch(100).unwrap().depth, 4);
     }
 }
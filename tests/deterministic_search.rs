@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use checkers_checkerboard::game::*;
+
+const POSITIONS: [&str; 3] = [
+    "B:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12",
+    "W:W18,19,21,22,23,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,9,11,12,14,20",
+    "B:WK7,21,27:B12,K19,22",
+];
+
+fn create_engine() -> Engine {
+    let mut engine = Engine::with_endgame_table(Duration::from_millis(20), EndgameTable::new());
+    engine.config = EngineConfig {
+        // Ignored by a deterministic search.
+        threads: 4,
+        ..EngineConfig::deterministic(42)
+    };
+    engine
+}
+
+fn search(fen: &str, limits: &SearchLimits) -> (MoveSequence, i32, usize) {
+    let mut game = Game::from_fen(fen).unwrap();
+    let mut engine = create_engine();
+    let score = engine.search_with_limits(&mut game, limits.clone());
+
+    (
+        engine.best_move.clone().unwrap(),
+        score,
+        engine.searched_nodes,
+    )
+}
+
+#[test]
+fn node_limited_searches_are_reproducible() {
+    let limits = SearchLimits::nodes(50_000);
+
+    for fen in POSITIONS {
+        let first = search(fen, &limits);
+        assert_eq!(first.2, 50_000, "{}", fen);
+
+        for _ in 0..2 {
+            assert_eq!(search(fen, &limits), first, "{}", fen);
+        }
+    }
+}
+
+#[test]
+fn time_limited_searches_are_reproducible() {
+    let limits = SearchLimits::clock(Duration::from_millis(500), Duration::ZERO, Some(10));
+
+    for fen in POSITIONS {
+        let first = search(fen, &limits);
+        for _ in 0..2 {
+            assert_eq!(search(fen, &limits), first, "{}", fen);
+        }
+    }
+}
+
+#[tokio::test]
+async fn find_best_move_is_reproducible() {
+    let mut results = vec![];
+
+    for _ in 0..3 {
+        let mut game = Game::from_fen(POSITIONS[1]).unwrap();
+        let mut engine = create_engine();
+        let engine_move = engine.find_best_move(&mut game).await;
+
+        results.push((engine_move, engine.searched_nodes));
+    }
+
+    assert_eq!(results[0], results[1]);
+    assert_eq!(results[0], results[2]);
+}